@@ -0,0 +1,92 @@
+#![feature(test)]
+
+extern crate test;
+extern crate rand;
+extern crate blissb;
+
+use test::Bencher;
+use rand::{ ChaChaRng, SeedableRng };
+use blissb::{ PrivateKey, PublicKey, Signature, BlissI, verify_batch_threads, sign_batch_threads };
+
+const BATCH: usize = 64;
+
+type Item = (PublicKey<BlissI>, Signature<BlissI>, [u8; 64]);
+
+
+fn items() -> Vec<Item> {
+    let mut rng = ChaChaRng::from_seed(&[0]);
+    (0..BATCH)
+        .map(|i| {
+            let sk = PrivateKey::<BlissI>::generate(&mut rng).unwrap();
+            let hash = [i as u8; 64];
+            (sk.public(), sk.sign(&mut rng, &hash).unwrap(), hash)
+        })
+        .collect()
+}
+
+fn bench_verify_batch(b: &mut Bencher, threads: usize) {
+    let items = items();
+    b.iter(|| assert!(verify_batch_threads(&items, threads).is_empty()));
+}
+
+fn bench_sign_batch(b: &mut Bencher, threads: usize) {
+    let sk = PrivateKey::<BlissI>::generate(&mut ChaChaRng::from_seed(&[0])).unwrap();
+    let hashes = (0..BATCH).map(|i| [i as u8; 64]).collect::<Vec<_>>();
+    b.iter(|| sign_batch_threads(&sk, &hashes, threads).unwrap());
+}
+
+#[bench]
+fn bench_verify_loop(b: &mut Bencher) {
+    let items = items();
+    b.iter(|| {
+        for (pk, sign, hash) in &items {
+            assert!(pk.verify(sign, hash));
+        }
+    });
+}
+
+#[bench]
+fn bench_verify_batch_1(b: &mut Bencher) {
+    bench_verify_batch(b, 1);
+}
+
+#[bench]
+fn bench_verify_batch_2(b: &mut Bencher) {
+    bench_verify_batch(b, 2);
+}
+
+#[bench]
+fn bench_verify_batch_4(b: &mut Bencher) {
+    bench_verify_batch(b, 4);
+}
+
+#[bench]
+fn bench_verify_batch_8(b: &mut Bencher) {
+    bench_verify_batch(b, 8);
+}
+
+#[bench]
+fn bench_sign_loop(b: &mut Bencher) {
+    let mut rng = ChaChaRng::from_seed(&[0]);
+    let sk = PrivateKey::<BlissI>::generate(&mut rng).unwrap();
+    b.iter(|| {
+        for i in 0..BATCH {
+            sk.sign(&mut rng, &[i as u8; 64]).unwrap();
+        }
+    });
+}
+
+#[bench]
+fn bench_sign_batch_1(b: &mut Bencher) {
+    bench_sign_batch(b, 1);
+}
+
+#[bench]
+fn bench_sign_batch_4(b: &mut Bencher) {
+    bench_sign_batch(b, 4);
+}
+
+#[bench]
+fn bench_sign_batch_8(b: &mut Bencher) {
+    bench_sign_batch(b, 8);
+}
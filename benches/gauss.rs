@@ -0,0 +1,30 @@
+#![feature(test)]
+
+extern crate test;
+extern crate rand;
+extern crate blissb;
+
+use test::Bencher;
+use rand::{ ChaChaRng, SeedableRng };
+use blissb::{ ParameterSet, BlissI, GaussianSampler, BernoulliSampler, CdtSampler, KnuthYaoSampler };
+
+
+fn bench_sampler<P: ParameterSet, S: GaussianSampler<P>>(b: &mut Bencher, sampler: &S) {
+    let mut rng = ChaChaRng::from_seed(&[0]);
+    b.iter(|| sampler.sample(&mut rng));
+}
+
+#[bench]
+fn bench_bernoulli(b: &mut Bencher) {
+    bench_sampler(b, &BernoulliSampler::<BlissI>::new());
+}
+
+#[bench]
+fn bench_cdt(b: &mut Bencher) {
+    bench_sampler(b, &CdtSampler::<BlissI>::new());
+}
+
+#[bench]
+fn bench_knuth_yao(b: &mut Bencher) {
+    bench_sampler(b, &KnuthYaoSampler::<BlissI>::new());
+}
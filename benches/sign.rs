@@ -0,0 +1,58 @@
+#![feature(test)]
+
+extern crate test;
+extern crate rand;
+extern crate blissb;
+
+use test::Bencher;
+use rand::{ ChaChaRng, SeedableRng };
+use blissb::{ ParameterSet, PrivateKey, Signer, BlissI, BlissIV };
+
+
+fn bench_sign<P: ParameterSet>(b: &mut Bencher) {
+    let mut rng = ChaChaRng::from_seed(&[0]);
+    let sk = PrivateKey::<P>::generate(&mut rng).unwrap();
+    b.iter(|| sk.sign(&mut rng, &[0; 64]).unwrap());
+}
+
+fn bench_verify<P: ParameterSet>(b: &mut Bencher) {
+    let mut rng = ChaChaRng::from_seed(&[0]);
+    let sk = PrivateKey::<P>::generate(&mut rng).unwrap();
+    let (pk, sign) = (sk.public(), sk.sign(&mut rng, &[0; 64]).unwrap());
+    b.iter(|| assert!(pk.verify(&sign, &[0; 64])));
+}
+
+#[bench]
+fn bench_sign_i(b: &mut Bencher) {
+    bench_sign::<BlissI>(b);
+}
+
+#[bench]
+fn bench_sign_iv(b: &mut Bencher) {
+    bench_sign::<BlissIV>(b);
+}
+
+#[bench]
+fn bench_verify_i(b: &mut Bencher) {
+    bench_verify::<BlissI>(b);
+}
+
+#[bench]
+fn bench_verify_iv(b: &mut Bencher) {
+    bench_verify::<BlissIV>(b);
+}
+
+#[bench]
+fn bench_verify_prepared_i(b: &mut Bencher) {
+    let mut rng = ChaChaRng::from_seed(&[0]);
+    let sk = PrivateKey::<BlissI>::generate(&mut rng).unwrap();
+    let (pk, sign) = (sk.public().prepare(), sk.sign(&mut rng, &[0; 64]).unwrap());
+    b.iter(|| assert!(pk.verify(&sign, &[0; 64])));
+}
+
+#[bench]
+fn bench_signer_i(b: &mut Bencher) {
+    let mut rng = ChaChaRng::from_seed(&[0]);
+    let mut signer = Signer::new(PrivateKey::<BlissI>::generate(&mut rng).unwrap(), rng);
+    b.iter(|| signer.sign(&[0; 64]).unwrap());
+}
@@ -0,0 +1,148 @@
+use ::bliss::{ PublicKey, Signature };
+use ::error::Error;
+use ::param::{ ParameterSet, Array, BlissI, BlissII, BlissIII, BlissIV, BlissV };
+#[cfg(feature = "o")]
+use ::param::Bliss0;
+
+
+/// Version of the header byte, in its high nibble. The low nibble is `ParameterSet::ID`.
+pub const HEADER_VERSION: u8 = 1;
+
+fn header<P: ParameterSet>() -> u8 {
+    (HEADER_VERSION << 4) | P::ID
+}
+
+/// Split the header off `input`, giving the set id and the rest.
+fn split_header(input: &[u8]) -> Result<(u8, &[u8]), Error> {
+    match input.first() {
+        Some(&h) if h >> 4 == HEADER_VERSION => Ok((h & 0x0f, &input[1..])),
+        Some(_) => Err(Error::Encoding { field: "header", index: 0 }),
+        None => Err(Error::Length { expected: 1, found: 0 })
+    }
+}
+
+/// Count the header in length errors of the inner `import`.
+fn with_header(err: Error) -> Error {
+    match err {
+        Error::Length { expected, found } => Error::Length { expected: expected + 1, found: found + 1 },
+        err => err
+    }
+}
+
+fn import_public<P: ParameterSet>(input: &[u8]) -> Result<PublicKey<P>, Error> {
+    if input.len() != P::PUBLICKEY_LENGTH {
+        return Err(Error::Length { expected: P::PUBLICKEY_LENGTH, found: input.len() });
+    }
+    let mut buf = P::PublicKeyBytes::zero();
+    buf.as_mut().copy_from_slice(input);
+    PublicKey::import(&buf)
+}
+
+macro_rules! any_type {
+    (
+        $(#[$attr:meta])* $any:ident, $inner:ident, $import:expr;
+        $( $(#[$vattr:meta])* $variant:ident => $set:ident ),*
+    ) => {
+        $(#[$attr])*
+        // like the inner types, the sets are kept inline rather than boxed, and the
+        // enum is only as large as the set with the largest `N`
+        #[allow(clippy::large_enum_variant)]
+        pub enum $any {
+            $( $(#[$vattr])* $variant($inner<$set>) ),*
+        }
+
+        $(
+            $(#[$vattr])*
+            impl From<$inner<$set>> for $any {
+                fn from(x: $inner<$set>) -> $any {
+                    $any::$variant(x)
+                }
+            }
+        )*
+
+        impl $any {
+            /// `ParameterSet::ID` of the set.
+            pub fn id(&self) -> u8 {
+                match *self {
+                    $( $(#[$vattr])* $any::$variant(_) => $set::ID ),*
+                }
+            }
+
+            pub fn name(&self) -> &'static str {
+                match *self {
+                    $( $(#[$vattr])* $any::$variant(_) => $set::NAME ),*
+                }
+            }
+
+            /// The header byte followed by the `export` of the set.
+            pub fn export(&self) -> Result<Vec<u8>, Error> {
+                match *self {
+                    $(
+                        $(#[$vattr])*
+                        $any::$variant(ref x) => {
+                            let bytes = x.export()?;
+                            let mut output = Vec::with_capacity(1 + bytes.as_ref().len());
+                            output.push(header::<$set>());
+                            output.extend_from_slice(bytes.as_ref());
+                            Ok(output)
+                        }
+                    ),*
+                }
+            }
+
+            /// Import any set, told apart by the header byte.
+            pub fn import(input: &[u8]) -> Result<$any, Error> {
+                let (id, body) = split_header(input)?;
+                match id {
+                    $(
+                        $(#[$vattr])*
+                        id if id == $set::ID => $import(body).map($any::$variant).map_err(with_header),
+                    )*
+                    _ => Err(Error::Encoding { field: "header", index: 0 })
+                }
+            }
+        }
+    }
+}
+
+any_type!(
+    /// A public key of any parameter set.
+    ///
+    /// Only the BLISS-B variant is covered: the header does not tell the variants apart,
+    /// so keys of `Original` are exchanged as `PublicKey<P, Original>`.
+    AnyPublicKey, PublicKey, import_public;
+    #[cfg(feature = "o")] O => Bliss0,
+    I => BlissI, II => BlissII, III => BlissIII, IV => BlissIV, V => BlissV
+);
+
+any_type!(
+    /// A signature of any parameter set, of the BLISS-B variant as `AnyPublicKey`.
+    AnySignature, Signature, Signature::import;
+    #[cfg(feature = "o")] O => Bliss0,
+    I => BlissI, II => BlissII, III => BlissIII, IV => BlissIV, V => BlissV
+);
+
+impl AnyPublicKey {
+    /// False if `sign` belongs to another set.
+    pub fn verify(&self, sign: &AnySignature, hash: &[u8]) -> bool {
+        match (self, sign) {
+            #[cfg(feature = "o")]
+            (AnyPublicKey::O(pk), AnySignature::O(sign)) => pk.verify(sign, hash),
+            (AnyPublicKey::I(pk), AnySignature::I(sign)) => pk.verify(sign, hash),
+            (AnyPublicKey::II(pk), AnySignature::II(sign)) => pk.verify(sign, hash),
+            (AnyPublicKey::III(pk), AnySignature::III(sign)) => pk.verify(sign, hash),
+            (AnyPublicKey::IV(pk), AnySignature::IV(sign)) => pk.verify(sign, hash),
+            (AnyPublicKey::V(pk), AnySignature::V(sign)) => pk.verify(sign, hash),
+            _ => false
+        }
+    }
+
+    /// Import a signature for this key, refusing one of another set.
+    pub fn import_signature(&self, input: &[u8]) -> Result<AnySignature, Error> {
+        let sign = AnySignature::import(input)?;
+        if sign.id() != self.id() {
+            return Err(Error::ParameterMismatch);
+        }
+        Ok(sign)
+    }
+}
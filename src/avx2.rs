@@ -0,0 +1,170 @@
+//! AVX2 versions of the transforms of `ntt.rs`, 16 coefficients at a time in two registers
+//! of 8 lanes. Every lane does the same 32-bit operations as the scalar code,
+//! so the results are identical.
+
+use std::arch::x86_64::*;
+use ::param::ParameterSet;
+use ::ntt::Reduce;
+
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn montgomery<P: ParameterSet>(x: __m256i) -> __m256i {
+    let m = _mm256_mullo_epi32(x, _mm256_set1_epi32(P::QINV));
+    let m = _mm256_srai_epi32(_mm256_slli_epi32(m, 16), 16);
+    _mm256_srai_epi32(_mm256_sub_epi32(x, _mm256_mullo_epi32(m, _mm256_set1_epi32(P::Q))), 16)
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn barrett<P: ParameterSet>(x: __m256i) -> __m256i {
+    let t = _mm256_mullo_epi32(x, _mm256_set1_epi32(P::BARRETT));
+    let t = _mm256_srai_epi32(_mm256_add_epi32(t, _mm256_set1_epi32(1 << 25)), 26);
+    _mm256_sub_epi32(x, _mm256_mullo_epi32(t, _mm256_set1_epi32(P::Q)))
+}
+
+/// `x + q` where `x < 0`.
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn canonical<P: ParameterSet>(x: __m256i) -> __m256i {
+    _mm256_add_epi32(x, _mm256_and_si256(_mm256_srai_epi32(x, 31), _mm256_set1_epi32(P::Q)))
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn ct<P: ParameterSet>(x: __m256i, y: __m256i, zeta: __m256i) -> (__m256i, __m256i) {
+    let t = montgomery::<P>(_mm256_mullo_epi32(zeta, y));
+    (_mm256_add_epi32(x, t), _mm256_sub_epi32(x, t))
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn gs<P: ParameterSet>(x: __m256i, y: __m256i, zeta: __m256i, reduce: bool) -> (__m256i, __m256i) {
+    let s = _mm256_add_epi32(x, y);
+    let s = if reduce { barrett::<P>(s) } else { s };
+    (s, montgomery::<P>(_mm256_mullo_epi32(zeta, _mm256_sub_epi32(x, y))))
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn load(v: &[i32]) -> __m256i {
+    _mm256_loadu_si256(v[..8].as_ptr() as *const __m256i)
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn store(v: &mut [i32], x: __m256i) {
+    _mm256_storeu_si256(v[..8].as_mut_ptr() as *mut __m256i, x)
+}
+
+/// Zetas of the layers with `len < 8` for the block `c` of 16 coefficients,
+/// in the lanes of the shuffles below.
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn small_zetas(zetas: &[i32], n: usize, c: usize) -> [__m256i; 3] {
+    let z4 = &zetas[n / 8 + 2 * c..];
+    let z2 = &zetas[n / 4 + 4 * c..];
+    let z1 = &zetas[n / 2 + 8 * c..];
+    [
+        _mm256_setr_epi32(z4[0], z4[0], z4[0], z4[0], z4[1], z4[1], z4[1], z4[1]),
+        _mm256_setr_epi32(z2[0], z2[0], z2[2], z2[2], z2[1], z2[1], z2[3], z2[3]),
+        _mm256_setr_epi32(z1[0], z1[1], z1[4], z1[5], z1[2], z1[3], z1[6], z1[7])
+    ]
+}
+
+/// See `ntt::forward_scalar`, for `n >= 16`.
+#[target_feature(enable = "avx2")]
+pub unsafe fn forward<P: ParameterSet>(v: &mut [i32]) {
+    let (n, zetas) = (P::N, P::ZETAS);
+
+    let mut len = n >> 1;
+    while len >= 8 {
+        let layer = &zetas[n / (2 * len)..];
+        for (&zeta, chunk) in layer.iter().zip(v.chunks_mut(2 * len)) {
+            let zeta = _mm256_set1_epi32(zeta);
+            let (lo, hi) = chunk.split_at_mut(len);
+            for i in (0..len).step_by(8) {
+                let (x, y) = ct::<P>(load(&lo[i..]), load(&hi[i..]), zeta);
+                store(&mut lo[i..], x);
+                store(&mut hi[i..], y);
+            }
+        }
+        len >>= 1;
+    }
+
+    // len = 4, 2, 1 within the two registers of a block, lo halves in `x` and hi halves in `y`
+    for (c, chunk) in v.chunks_mut(16).enumerate() {
+        let z = small_zetas(zetas, n, c);
+        let (a, b) = (load(&chunk[..8]), load(&chunk[8..]));
+
+        let (x, y) = ct::<P>(_mm256_permute2x128_si256(a, b, 0x20), _mm256_permute2x128_si256(a, b, 0x31), z[0]);
+        let (a, b) = (_mm256_permute2x128_si256(x, y, 0x20), _mm256_permute2x128_si256(x, y, 0x31));
+
+        let (x, y) = ct::<P>(_mm256_unpacklo_epi64(a, b), _mm256_unpackhi_epi64(a, b), z[1]);
+        let (a, b) = (_mm256_unpacklo_epi64(x, y), _mm256_unpackhi_epi64(x, y));
+
+        let (a, b) = (_mm256_shuffle_epi32(a, 0xd8), _mm256_shuffle_epi32(b, 0xd8));
+        let (x, y) = ct::<P>(_mm256_unpacklo_epi64(a, b), _mm256_unpackhi_epi64(a, b), z[2]);
+        let (a, b) = (_mm256_unpacklo_epi64(x, y), _mm256_unpackhi_epi64(x, y));
+        let (a, b) = (_mm256_shuffle_epi32(a, 0xd8), _mm256_shuffle_epi32(b, 0xd8));
+
+        store(&mut chunk[..8], barrett::<P>(a));
+        store(&mut chunk[8..], barrett::<P>(b));
+    }
+}
+
+/// See `ntt::inverse_scalar`, for `n >= 16`.
+#[target_feature(enable = "avx2")]
+pub unsafe fn inverse<P: ParameterSet>(v: &mut [i32]) {
+    let (n, zetas) = (P::N, P::ZETAS_INV);
+
+    // len = 1, 2, 4, reducing after len = 2
+    for (c, chunk) in v.chunks_mut(16).enumerate() {
+        let z = small_zetas(zetas, n, c);
+        let (a, b) = (load(&chunk[..8]), load(&chunk[8..]));
+
+        let (a, b) = (_mm256_shuffle_epi32(a, 0xd8), _mm256_shuffle_epi32(b, 0xd8));
+        let (x, y) = gs::<P>(_mm256_unpacklo_epi64(a, b), _mm256_unpackhi_epi64(a, b), z[2], false);
+        let (a, b) = (_mm256_unpacklo_epi64(x, y), _mm256_unpackhi_epi64(x, y));
+        let (a, b) = (_mm256_shuffle_epi32(a, 0xd8), _mm256_shuffle_epi32(b, 0xd8));
+
+        let (x, y) = gs::<P>(_mm256_unpacklo_epi64(a, b), _mm256_unpackhi_epi64(a, b), z[1], true);
+        let (a, b) = (_mm256_unpacklo_epi64(x, y), _mm256_unpackhi_epi64(x, y));
+
+        let (x, y) = gs::<P>(_mm256_permute2x128_si256(a, b, 0x20), _mm256_permute2x128_si256(a, b, 0x31), z[0], false);
+        let (a, b) = (_mm256_permute2x128_si256(x, y, 0x20), _mm256_permute2x128_si256(x, y, 0x31));
+
+        store(&mut chunk[..8], a);
+        store(&mut chunk[8..], b);
+    }
+
+    let mut len = 8;
+    while len < n {
+        let reduce = len.trailing_zeros() & 1 != 0;
+        let layer = &zetas[n / (2 * len)..];
+        for (&zeta, chunk) in layer.iter().zip(v.chunks_mut(2 * len)) {
+            let zeta = _mm256_set1_epi32(zeta);
+            let (lo, hi) = chunk.split_at_mut(len);
+            for i in (0..len).step_by(8) {
+                let (x, y) = gs::<P>(load(&lo[i..]), load(&hi[i..]), zeta, reduce);
+                store(&mut lo[i..], x);
+                store(&mut hi[i..], y);
+            }
+        }
+        len <<= 1;
+    }
+
+    let scale = _mm256_set1_epi32(P::SCALE);
+    for chunk in v.chunks_mut(8) {
+        let x = montgomery::<P>(_mm256_mullo_epi32(scale, load(chunk)));
+        store(chunk, canonical::<P>(x));
+    }
+}
+
+/// See `ntt::pointwise_scalar`.
+#[target_feature(enable = "avx2")]
+pub unsafe fn pointwise<P: ParameterSet>(v: &mut [i32], a: &[i32]) {
+    for (x, y) in v.chunks_mut(8).zip(a.chunks(8)) {
+        store(x, montgomery::<P>(_mm256_mullo_epi32(load(x), load(y))));
+    }
+}
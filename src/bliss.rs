@@ -1,272 +1,820 @@
-use std::io;
-use rand::{ Rand, Rng, OsRng };
-use rand::distributions::{ Normal, Sample };
+use std::{ fmt, mem, slice };
+use std::marker::PhantomData;
+use std::sync::{ Arc, Mutex, Condvar };
+use std::sync::atomic::{ AtomicBool, Ordering };
+use std::thread::{ self, JoinHandle };
+use rand::{ Rand, Rng, OsRng, ChaChaRng };
+use tiny_keccak::Keccak;
+use byteorder::{ LittleEndian, ByteOrder };
 use bitpack::BitPack;
-use ::ntt::{ fft, flp, xmu, cmu, pwr };
-use ::param::*;
+use ::error::Error;
+use ::rng::{ CryptoRng, ShakeRng };
+use ::gauss::{ GaussianSampler, DefaultSampler, bernoulli_exp, bernoulli_cosh };
+use ::ntt::{ ntt, mul, prepare, pwr };
+use ::param::{ ParameterSet, Array, FORMAT_FG, FORMAT_SEED, PRIVATEKEY_SEED_LENGTH };
+use ::variant::{ Variant, BlissB };
 use ::utils::{
-    uniform_poly, c_oracle, greedy_sc,
-    vecabsmax, vecscalar
+    uniform_poly, c_oracle,
+    vecabsmax, vecnorm, vecscalar, scrub, scrub_value, Sparse
 };
 
 
-pub struct PrivateKey {
-    pub f: [i32; N],
-    pub g: [i32; N],
-    pub a: [i32; N]
+/// A private key of the set `P` for the signing variant `V`.
+/// The fields are read-only: signing keeps the nonzero positions of `f` and `g` alongside,
+/// and `a` and the seed must stay the ones derived from them.
+pub struct PrivateKey<P: ParameterSet, V: Variant<P> = BlissB> {
+    f: P::Poly,
+    g: P::Poly,
+    a: P::Poly,
+    /// The seed of `from_seed`, if the key came from one.
+    seed: Option<[u8; 32]>,
+    /// Built from `f` and `g` with the key.
+    sparse: Sparse<P>,
+    variant: PhantomData<V>
 }
 
-pub struct PublicKey {
-    pub a: [i32; N]
+pub struct PublicKey<P: ParameterSet, V: Variant<P> = BlissB> {
+    pub a: P::Poly,
+    variant: PhantomData<V>
 }
 
-pub struct Signature {
-    pub t: [i32; N],
-    pub z: [i32; N],
-    pub c_idx: [usize; KAPPA]
+pub struct Signature<P: ParameterSet, V: Variant<P> = BlissB> {
+    pub t: P::Poly,
+    pub z: P::Poly,
+    pub c_idx: P::Index,
+    variant: PhantomData<V>
 }
 
-impl PrivateKey {
-    pub fn new<R: Rand + Rng>() -> io::Result<PrivateKey> {
-        let mut rng = OsRng::new()?.gen::<R>();
-        let (mut t, mut u, mut a) = ([0; N], [0; N], [0; N]);
-        let mut privkey = PrivateKey {
-            f: [0; N],
-            g: [0; N],
-            a: [0; N]
-        };
+/// A public key with `a` cached in the layout of the transforms, for verifying many signatures.
+pub struct PreparedPublicKey<P: ParameterSet, V: Variant<P> = BlissB> {
+    a: P::Poly,
+    variant: PhantomData<V>
+}
+
+/// A private key with `a`, the PRF key of derandomized signing and the default sampler cached.
+pub struct PreparedPrivateKey<P: ParameterSet, V: Variant<P> = BlissB> {
+    key: PrivateKey<P, V>,
+    a: P::Poly,
+    prf_key: [u8; 32],
+    sampler: DefaultSampler<P>
+}
+
+/// Signing state kept across calls: the prepared key with its sampler tables, the RNG
+/// and the scratch buffers, boxed so that the stack holds none of them.
+/// Signing allocates and initialises nothing and needs at most `STACK_BUDGET` bytes of stack.
+pub struct Signer<P: ParameterSet, R: CryptoRng, V: Variant<P> = BlissB> {
+    key: PreparedPrivateKey<P, V>,
+    rng: R,
+    scratch: Box<Scratch<P, V>>
+}
+
+// `Signer::STACK_BUDGET` is `STACK_BASE` bytes and `STACK_POLYS` polynomials, enough for
+// unoptimized builds which keep a copy of the signature in most frames; with optimizations
+// signing takes under a third of it.
+const STACK_BASE: usize = 32 * 1024;
+const STACK_POLYS: usize = 20;
+
+/// The half of a signing attempt that does not depend on the message:
+/// `y1, y2` from the sampler, `v = zeta a y1 + y2 mod 2q` and `z` the rounding of `v`.
+/// Signing with the same commitment twice reveals the key, so it is never copied.
+struct Commitment<P: ParameterSet> {
+    y1: P::Poly,
+    y2: P::Poly,
+    v: P::Poly,
+    z: P::Poly
+}
+
+/// Buffers of a signing attempt.
+struct Scratch<P: ParameterSet, V: Variant<P>> {
+    com: Commitment<P>,
+    x: P::Poly,
+    y: P::Poly,
+    sign: Signature<P, V>
+}
+
+/// Commitments of an `OnlineSigner`, shared with its background thread.
+struct Pool<P: ParameterSet> {
+    entries: Mutex<Vec<Box<Commitment<P>>>>,
+    capacity: usize,
+    /// Signalled when an entry is taken or `stop` is set.
+    taken: Condvar,
+    stop: AtomicBool
+}
+
+/// Signing split into an offline phase, which fills a bounded pool with commitments,
+/// and an online phase, which only runs the oracle, `GreedySC` and the rejection step.
+/// Signing takes commitments out of the pool, so each is used for one attempt at most,
+/// and computes them on the spot when the pool is empty.
+pub struct OnlineSigner<P: ParameterSet, R: CryptoRng, V: Variant<P> = BlissB> {
+    key: Arc<PreparedPrivateKey<P, V>>,
+    pool: Arc<Pool<P>>,
+    rng: R,
+    scratch: Box<Scratch<P, V>>,
+    worker: Option<JoinHandle<()>>
+}
 
-        uniform_poly(&mut privkey.g, &mut rng);
-        for i in 0..N {
-            privkey.g[i] *= 2;
+impl<P: ParameterSet> Commitment<P> {
+    fn zero() -> Commitment<P> {
+        Commitment {
+            y1: P::Poly::zero(),
+            y2: P::Poly::zero(),
+            v: P::Poly::zero(),
+            z: P::Poly::zero()
         }
-        privkey.g[0] -= 1;
-        xmu(&mut t, &privkey.g, &W);
-        fft(&mut t);
+    }
 
-        'f : for _ in 0..1024 {
-            uniform_poly(&mut privkey.f, &mut rng);
-            xmu(&mut u, &privkey.f, &W);
-            fft(&mut u);
+    /// `a` is from `prepare_a`.
+    fn fill<R: Rng, S: GaussianSampler<P>>(&mut self, a: &P::Poly, sampler: &S, rng: &mut R) {
+        let (q, n, d, p) = (P::Q, P::N, P::D, P::P);
 
-            for i in 0..N {
-                let x = u[i] % Q;
-                if x == 0 { continue 'f };
-                u[i] = pwr(x, Q - 2, Q);
-            }
+        for i in 0..n {
+            self.y1[i] = sampler.sample(rng);
+            self.y2[i] = sampler.sample(rng);
+        }
 
-            xmu(&mut a, &t, &u);
-            fft(&mut a);
-            xmu(&mut privkey.a, &a, &R);
+        mul::<P>(self.v.as_mut(), self.y1.as_ref(), a.as_ref());
 
-            cmu(&mut a, &privkey.a, -1);
-            flp(&mut a);
-            xmu(&mut privkey.a, &a, &W);
-            fft(&mut privkey.a);
+        for i in 0..n {
+            let mut tmp = self.v[i];
+            if tmp & 1 != 0 { tmp += q };
+            tmp = (tmp + self.y2[i]) % (2 * q);
+            if tmp < 0 { tmp += 2 * q };
+            self.v[i] = tmp;
+            self.z[i] = ((tmp + (1 << (d - 1))) >> d) % p;
+        }
+    }
+}
 
-            for i in 0..N {
-                let x = privkey.a[i] % Q;
-                privkey.a[i] = if x < 0 { x + Q } else { x };
-            }
+impl<P: ParameterSet> Drop for Commitment<P> {
+    fn drop(&mut self) {
+        scrub(self.y1.as_mut());
+        scrub(self.y2.as_mut());
+        scrub(self.v.as_mut());
+        scrub(self.z.as_mut());
+    }
+}
+
+impl<P: ParameterSet> Pool<P> {
+    fn take(&self) -> Option<Box<Commitment<P>>> {
+        let entry = self.entries.lock().unwrap().pop();
+        self.taken.notify_one();
+        entry
+    }
 
-            return Ok(privkey);
+    /// Wait for room in the pool, false once stopped.
+    fn wait_room(&self) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        while entries.len() >= self.capacity && !self.stop.load(Ordering::SeqCst) {
+            entries = self.taken.wait(entries).unwrap();
         }
+        !self.stop.load(Ordering::SeqCst)
+    }
 
-        Err(io::Error::new(io::ErrorKind::Other, "Unable to generate the correct private key."))
+    /// Add `entry` if there is room, giving it back otherwise.
+    fn push(&self, entry: Box<Commitment<P>>) -> Option<Box<Commitment<P>>> {
+        let mut entries = self.entries.lock().unwrap();
+        if entries.len() < self.capacity {
+            entries.push(entry);
+            None
+        } else {
+            Some(entry)
+        }
     }
+}
 
-    pub fn public(&self) -> PublicKey {
-        let mut pubkey = PublicKey {
-            a: [0; N]
-        };
-        pubkey.a.copy_from_slice(&self.a);
-        pubkey
-    }
-
-    pub fn signature<R: Rand + Rng>(&self, hash: &[u8]) -> io::Result<Signature> {
-        let mut u = [0; N];
-        let (mut v, mut vv) = ([0; N], [0; N]);
-        let (mut x, mut y) = ([0; N], [0; N]);
-        let mut sign = Signature {
-            t: [0; N],
-            z: [0; N],
-            c_idx: [0; KAPPA]
-        };
-        let mut rng = OsRng::new()?.gen::<R>();
-        let mut sample = Normal::new(0.0, SIGMA);
+impl<P: ParameterSet, V: Variant<P>> Scratch<P, V> {
+    fn new() -> Scratch<P, V> {
+        Scratch {
+            com: Commitment::zero(),
+            x: P::Poly::zero(),
+            y: P::Poly::zero(),
+            sign: Signature::zero()
+        }
+    }
+
+    fn take_sign(&mut self) -> Signature<P, V> {
+        mem::replace(&mut self.sign, Signature::zero())
+    }
+}
+
+impl<P: ParameterSet, V: Variant<P>> Drop for Scratch<P, V> {
+    fn drop(&mut self) {
+        scrub(self.x.as_mut());
+        scrub(self.y.as_mut());
+        scrub(self.sign.t.as_mut());
+        scrub(self.sign.z.as_mut());
+    }
+}
 
-        macro_rules! gauss_sample {
-            () => { sample.sample(&mut rng) as i32 }
+impl<P: ParameterSet, V: Variant<P>> Drop for PrivateKey<P, V> {
+    fn drop(&mut self) {
+        scrub(self.f.as_mut());
+        scrub(self.g.as_mut());
+        if let Some(ref mut seed) = self.seed {
+            scrub(seed);
         }
+    }
+}
+
+impl<P: ParameterSet, V: Variant<P>> Drop for PreparedPrivateKey<P, V> {
+    fn drop(&mut self) {
+        scrub(&mut self.prf_key);
+    }
+}
+
+impl<P: ParameterSet, V: Variant<P>> fmt::Debug for PreparedPrivateKey<P, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PreparedPrivateKey")
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+impl<P: ParameterSet, V: Variant<P>> fmt::Debug for PrivateKey<P, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PrivateKey")
+            .field("params", &P::NAME)
+            .field("variant", &V::NAME)
+            .field("f", &"<redacted>")
+            .field("g", &"<redacted>")
+            .field("a", &self.a.as_ref())
+            .field("seed", &self.seed.map(|_| "<redacted>"))
+            .finish()
+    }
+}
+
+fn write(bitpack: &mut BitPack<&mut [u8]>, value: i32, bits: usize, field: &'static str, index: usize)
+    -> Result<(), Error>
+{
+    if value < 0 || value >> bits != 0 {
+        return Err(Error::Encoding { field, index });
+    }
+    bitpack.write(value as u32, bits)
+        .map_err(|_| Error::Encoding { field, index })
+}
+
+/// `a = -g / f` in the NTT domain, false if `f` is not invertible.
+fn derive_a<P: ParameterSet>(f: &[i32], g: &[i32], a: &mut [i32]) -> bool {
+    let (q, n) = (P::Q, P::N);
+    let mut u = P::Poly::zero();
+
+    a.copy_from_slice(g);
+    ntt::<P>(a);
+    u.as_mut().copy_from_slice(f);
+    ntt::<P>(u.as_mut());
+
+    let mut invertible = true;
+    for i in 0..n {
+        if u[i] == 0 { invertible = false };
+        let x = (a[i] as i64 * pwr(u[i], q - 2, q) as i64 % q as i64) as i32;
+        a[i] = if x == 0 { 0 } else { q - x };
+    }
+
+    if !invertible { scrub(a) };
+    scrub(u.as_mut());
+    invertible
+}
+
+/// The bounds on `t` and `z << D` checked by the verifier.
+fn check_norms<P: ParameterSet, V: Variant<P>>(t: &[i32], z: &[i32]) -> bool {
+    // In i64: the fields of a forged signature can be anything up to i32::MIN.
+    let (d, b_inf) = (P::D, P::B_INF as i64);
+    vecabsmax(t) <= b_inf && (vecabsmax(z) << d) <= b_inf
+        && vecnorm(t) + (vecnorm(z) << (2 * d)) <= V::B_L2 as i64
+}
+
+/// Check that `v` has `NZ1` coefficients of `+-1`, `NZ2` of `+-2` and zeros elsewhere.
+fn check_shape<P: ParameterSet>(v: &[i32], field: &'static str) -> Result<(), Error> {
+    let (mut nz1, mut nz2) = (0, 0);
+    for (i, &x) in v.iter().enumerate() {
+        match x.abs() {
+            0 => (),
+            1 => nz1 += 1,
+            2 => nz2 += 1,
+            _ => return Err(Error::Encoding { field, index: i })
+        }
+        if nz1 > P::NZ1 || nz2 > P::NZ2 {
+            return Err(Error::Encoding { field, index: i });
+        }
+    }
+
+    if nz1 != P::NZ1 || nz2 != P::NZ2 {
+        return Err(Error::Encoding { field, index: v.len() - 1 });
+    }
+    Ok(())
+}
+
+/// `f` is uniform, `g` is `2 g' - 1` for a uniform `g'`, and the variant accepts them.
+fn check_fg<P: ParameterSet, V: Variant<P>>(f: &[i32], g: &[i32]) -> Result<(), Error> {
+    check_shape::<P>(f, "f")?;
+
+    let mut h = P::Poly::zero();
+    for i in 0..P::N {
+        let x = g[i] + if i == 0 { 1 } else { 0 };
+        if x & 1 != 0 {
+            return Err(Error::Encoding { field: "g", index: i });
+        }
+        h[i] = x / 2;
+    }
+    let result = check_shape::<P>(h.as_ref(), "g");
+    scrub(h.as_mut());
+    result?;
+
+    if !V::accept_key(f, g) {
+        return Err(Error::Encoding { field: "f", index: 0 });
+    }
+    Ok(())
+}
+
+/// Verify with `a` from `ntt::prepare`.
+fn verify<P: ParameterSet, V: Variant<P>>(a: &P::Poly, sign: &Signature<P, V>, hash: &[u8]) -> bool {
+    let (q, n, d, p) = (P::Q, P::N, P::D, P::P);
+
+    if !check_norms::<P, V>(sign.t.as_ref(), sign.z.as_ref()) {
+        return false;
+    }
+
+    // c_idx indexes v, and as for import it must be strictly increasing below N
+    let c_idx = sign.c_idx.as_ref();
+    if (0..P::KAPPA).any(|i| c_idx[i] >= n || (i > 0 && c_idx[i] <= c_idx[i - 1])) {
+        return false;
+    }
+
+    let mut v = P::Poly::zero();
+    let mut my_idx = P::Index::zero();
+
+    mul::<P>(v.as_mut(), sign.t.as_ref(), a.as_ref());
+
+    for i in 0..n {
+        if v[i] & 1 != 0 {
+            v[i] += q;
+        }
+    }
+
+    for &i in sign.c_idx.as_ref() {
+        v[i] = (v[i] + q) % (2 * q);
+    }
+
+    for i in 0..n {
+        let tmp = (((v[i] + (1 << (d - 1))) >> d) + sign.z[i]) % p;
+        v[i] = if tmp < 0 { tmp + p } else { tmp };
+    }
+
+    if !c_oracle::<P>(my_idx.as_mut(), hash, v.as_ref()) {
+        return false;
+    }
+
+    let mut d = 0;
+    for i in 0..P::KAPPA {
+        d |= my_idx[i] ^ sign.c_idx[i];
+    }
+    d == 0
+}
+
+/// Run `sign` with 32 fresh bytes of `rng` as the extra input of derandomized signing,
+/// the hedge of every `sign_hedged`.
+fn hedged<R: CryptoRng, T, F: FnOnce(&[u8]) -> T>(rng: &mut R, sign: F) -> T {
+    let mut extra = [0; 32];
+    rng.fill_bytes(&mut extra);
+    let result = sign(&extra);
+    scrub(&mut extra);
+    result
+}
+
+fn read(bitpack: &mut BitPack<&[u8]>, bits: usize, field: &'static str, index: usize)
+    -> Result<i32, Error>
+{
+    bitpack.read(bits)
+        .map(|x| x as i32)
+        .map_err(|_| Error::Encoding { field, index })
+}
+
+
+impl<P: ParameterSet, V: Variant<P>> PrivateKey<P, V> {
+    fn zero() -> PrivateKey<P, V> {
+        PrivateKey {
+            f: P::Poly::zero(),
+            g: P::Poly::zero(),
+            a: P::Poly::zero(),
+            seed: None,
+            sparse: Sparse::zero(),
+            variant: PhantomData
+        }
+    }
+
+    fn index(&mut self) {
+        self.sparse.fill(self.f.as_ref(), self.g.as_ref());
+    }
+
+    pub fn f(&self) -> &P::Poly {
+        &self.f
+    }
+
+    pub fn g(&self) -> &P::Poly {
+        &self.g
+    }
+
+    /// The public key, as it was imported or derived from `f` and `g`.
+    pub fn a(&self) -> &P::Poly {
+        &self.a
+    }
+
+    /// The seed of `from_seed`, if the key came from one.
+    pub fn seed(&self) -> Option<&[u8; 32]> {
+        self.seed.as_ref()
+    }
+
+    pub fn new<R: Rand + Rng>() -> Result<PrivateKey<P, V>, Error> {
+        PrivateKey::keygen(&mut OsRng::new()?.gen::<R>())
+    }
+
+    pub fn generate<R: CryptoRng>(rng: &mut R) -> Result<PrivateKey<P, V>, Error> {
+        PrivateKey::keygen(rng)
+    }
+
+    /// Expand `seed` with SHAKE256, the same seed gives the same key on every platform.
+    pub fn from_seed(seed: &[u8; 32]) -> Result<PrivateKey<P, V>, Error> {
+        let mut privkey = PrivateKey::keygen(&mut ShakeRng::new(&[b"blissb keygen", V::KEYGEN_TAG, P::NAME.as_bytes(), seed]))?;
+        privkey.seed = Some(*seed);
+        Ok(privkey)
+    }
+
+    fn keygen<R: Rng>(rng: &mut R) -> Result<PrivateKey<P, V>, Error> {
+        let mut privkey = PrivateKey::<P, V>::zero();
 
         for _ in 0..1024 {
-            for i in 0..N {
-                sign.t[i] = gauss_sample!();
-                u[i] = gauss_sample!();
+            uniform_poly::<P>(privkey.g.as_mut(), rng);
+            for i in 0..P::N {
+                privkey.g[i] *= 2;
             }
+            privkey.g[0] -= 1;
 
-            xmu(&mut v, &sign.t, &W);
-            fft(&mut v);
-            xmu(&mut vv, &v, &self.a);
-            fft(&mut vv);
-            xmu(&mut v, &vv, &R);
-            flp(&mut v);
-
-            for i in 0..N {
-                let mut tmp = v[i];
-                if tmp & 1 != 0 { tmp += Q };
-                tmp = (tmp + u[i]) % (2 * Q);
-                if tmp < 0 { tmp += 2 * Q };
-                v[i] = tmp;
-                sign.z[i] = ((tmp + (1 << (D - 1))) >> D) % P;
+            let mut invertible = false;
+            for _ in 0..1024 {
+                uniform_poly::<P>(privkey.f.as_mut(), rng);
+                if derive_a::<P>(privkey.f.as_ref(), privkey.g.as_ref(), privkey.a.as_mut()) {
+                    invertible = true;
+                    break;
+                }
             }
+            if !invertible { break };
 
-            if !c_oracle(&mut sign.c_idx, hash, &sign.z) { continue };
-            greedy_sc(&self.f, &self.g, &sign.c_idx, &mut x, &mut y);
-
-            if rng.gen() {
-                for i in 0..N {
-                    sign.t[i] -= x[i];
-                    u[i] -= y[i];
-                }
-            } else {
-                for i in 0..N {
-                    sign.t[i] += x[i];
-                    u[i] += y[i];
-                }
+            if V::accept_key(privkey.f.as_ref(), privkey.g.as_ref()) {
+                privkey.index();
+                return Ok(privkey);
             }
+        }
+
+        Err(Error::KeygenExhausted)
+    }
+
+    /// Build the private key from `f` and `g`, checking their shape and computing `a`.
+    pub fn from_fg(f: &P::Poly, g: &P::Poly) -> Result<PrivateKey<P, V>, Error> {
+        let mut privkey = PrivateKey::<P, V>::zero();
+        privkey.f = *f;
+        privkey.g = *g;
+
+        check_fg::<P, V>(privkey.f.as_ref(), privkey.g.as_ref())?;
+        if !derive_a::<P>(privkey.f.as_ref(), privkey.g.as_ref(), privkey.a.as_mut()) {
+            return Err(Error::Encoding { field: "f", index: 0 });
+        }
+
+        privkey.index();
+        Ok(privkey)
+    }
+
+    /// Check that `f` and `g` have the shape produced by key generation and that
+    /// `a` is the public key derived from them.
+    pub fn validate(&self) -> Result<(), Error> {
+        check_fg::<P, V>(self.f.as_ref(), self.g.as_ref())?;
+
+        let mut a = P::Poly::zero();
+        if !derive_a::<P>(self.f.as_ref(), self.g.as_ref(), a.as_mut()) {
+            return Err(Error::Encoding { field: "f", index: 0 });
+        }
+        match (0..P::N).find(|&i| a[i] != self.a[i]) {
+            Some(i) => Err(Error::Encoding { field: "a", index: i }),
+            None => Ok(())
+        }
+    }
+
+    pub fn public(&self) -> PublicKey<P, V> {
+        PublicKey {
+            a: self.a,
+            variant: PhantomData
+        }
+    }
+
+    pub fn signature<R: Rand + Rng>(&self, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        self.signature_with::<R, _>(&DefaultSampler::default(), hash)
+    }
+
+    pub fn signature_with<R: Rand + Rng, S: GaussianSampler<P>>(&self, sampler: &S, hash: &[u8])
+        -> Result<Signature<P, V>, Error>
+    {
+        self.sign_rng(&self.prepare_a(), sampler, &mut OsRng::new()?.gen::<R>(), hash, &mut Scratch::new())
+    }
+
+    pub fn sign<R: CryptoRng>(&self, rng: &mut R, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        self.sign_rng(&self.prepare_a(), &DefaultSampler::default(), rng, hash, &mut Scratch::new())
+    }
+
+    pub fn sign_with<R: CryptoRng, S: GaussianSampler<P>>(&self, sampler: &S, rng: &mut R, hash: &[u8])
+        -> Result<Signature<P, V>, Error>
+    {
+        self.sign_rng(&self.prepare_a(), sampler, rng, hash, &mut Scratch::new())
+    }
+
+    /// Derandomized signing, every random choice is drawn from a PRF over the private key,
+    /// the message hash and the attempt counter, so signing `hash` always gives the same signature.
+    pub fn sign_deterministic(&self, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        self.sign_prf(&DefaultSampler::default(), &[], hash)
+    }
+
+    /// Derandomized signing hedged with fresh randomness from `rng`,
+    /// which stays secure when `rng` is weak or broken.
+    pub fn sign_hedged<R: CryptoRng>(&self, rng: &mut R, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        hedged(rng, |extra| self.sign_prf(&DefaultSampler::default(), extra, hash))
+    }
+
+    /// Derandomized signing with caller-supplied extra randomness, which may be empty.
+    pub fn sign_prf<S: GaussianSampler<P>>(&self, sampler: &S, extra: &[u8], hash: &[u8])
+        -> Result<Signature<P, V>, Error>
+    {
+        let mut key = self.prf_key();
+        let result = self.sign_prf_with(&self.prepare_a(), &key, sampler, extra, hash, &mut Scratch::new());
+        scrub(&mut key);
+        result
+    }
 
-            let mut d = 1.0 / (SIGMA * SIGMA);
-            d = 1.0 / (
-                M *
-                (-0.5 * d * (vecscalar(&x, &x) + vecscalar(&y, &y)) as f64).exp() *
-                (d * (vecscalar(&sign.t, &x) + vecscalar(&u, &y)) as f64).cosh()
-            );
+    /// Cache what signing recomputes for every signature.
+    pub fn prepare(self) -> PreparedPrivateKey<P, V> {
+        PreparedPrivateKey {
+            a: self.prepare_a(),
+            prf_key: self.prf_key(),
+            sampler: DefaultSampler::default(),
+            key: self
+        }
+    }
 
-            if rng.gen::<f64>() > d { continue };
+    /// `a` laid out for `ntt::mul`.
+    fn prepare_a(&self) -> P::Poly {
+        let mut a = P::Poly::zero();
+        prepare::<P>(a.as_mut(), self.a.as_ref());
+        a
+    }
+
+    fn prf_key(&self) -> [u8; 32] {
+        let mut key = [0; 32];
+        let mut shake = Keccak::new_shake256();
+        shake.update(b"blissb prf key");
+        for i in 0..P::N {
+            shake.update(&[self.f[i] as u8, self.g[i] as u8]);
+        }
+        // squeezed in place instead of by `finalize`, which would leave a copy behind
+        shake.pad();
+        shake.keccakf();
+        shake.squeeze(&mut key);
+        unsafe { scrub_value(&mut shake) };
+        key
+    }
 
-            for i in 0..N {
-                let mut tmp = v[i] - u[i];
-                if tmp < 0 { tmp += 2 * Q };
-                if tmp >= 2 * Q { tmp -= 2 * Q };
+    fn sign_prf_with<S: GaussianSampler<P>>(&self, a: &P::Poly, key: &[u8; 32], sampler: &S, extra: &[u8], hash: &[u8], scratch: &mut Scratch<P, V>)
+        -> Result<Signature<P, V>, Error>
+    {
+        let mut extra_len = [0; 8];
+        LittleEndian::write_u64(&mut extra_len, extra.len() as u64);
 
-                tmp = ((tmp + (1 << (D - 1))) >> D) % P;
+        for attempt in 0..1024 {
+            let mut counter = [0; 4];
+            LittleEndian::write_u32(&mut counter, attempt);
+            let mut rng = ShakeRng::new(&[b"blissb sign", key, &counter, &extra_len, extra, hash]);
 
-                tmp = sign.z[i] - tmp;
-                if tmp < -P / 2 { tmp += P };
-                if tmp > P / 2 { tmp -= P };
-                sign.z[i] = tmp;
+            if self.attempt(a, sampler, &mut rng, hash, scratch) {
+                return Ok(scratch.take_sign());
             }
+        }
 
-            return Ok(sign);
+        Err(Error::SignExhausted)
+    }
+
+    fn sign_rng<R: Rng, S: GaussianSampler<P>>(&self, a: &P::Poly, sampler: &S, rng: &mut R, hash: &[u8], scratch: &mut Scratch<P, V>)
+        -> Result<Signature<P, V>, Error>
+    {
+        for _ in 0..1024 {
+            if self.attempt(a, sampler, rng, hash, scratch) {
+                return Ok(scratch.take_sign());
+            }
         }
 
-        Err(io::Error::new(io::ErrorKind::Other, "Unable to generate the correct signature."))
+        Err(Error::SignExhausted)
+    }
+
+    /// One round of signing with `a` from `prepare_a`, true if `scratch.sign` passed the rejection step.
+    fn attempt<R: Rng, S: GaussianSampler<P>>(&self, a: &P::Poly, sampler: &S, rng: &mut R, hash: &[u8], scratch: &mut Scratch<P, V>)
+        -> bool
+    {
+        let Scratch { ref mut com, ref mut x, ref mut y, ref mut sign } = *scratch;
+        com.fill(a, sampler, rng);
+        self.respond(com, rng, hash, x, y, sign)
     }
 
-    pub fn export(&self) -> Result<[u8; PRIVATEKEY_LENGTH], usize> {
-        let mut output = [0; PRIVATEKEY_LENGTH];
+    /// The message dependent half of `attempt`, which spends `com`.
+    fn respond<R: Rng>(&self, com: &mut Commitment<P>, rng: &mut R, hash: &[u8],
+        x: &mut P::Poly, y: &mut P::Poly, sign: &mut Signature<P, V>)
+        -> bool
+    {
+        let (q, n, d, p) = (P::Q, P::N, P::D, P::P);
+        let Commitment { ref y1, ref mut y2, ref v, ref z } = *com;
+
+        if !c_oracle::<P>(sign.c_idx.as_mut(), hash, z.as_ref()) { return false };
+        V::sc(self.f.as_ref(), self.g.as_ref(), &self.sparse, sign.c_idx.as_ref(), x.as_mut(), y.as_mut());
+
+        // z = y + Sc or y - Sc, without branching on the sign
+        let mut mask = -((rng.next_u32() & 1) as i32);
+        for i in 0..n {
+            sign.t[i] = y1[i] + ((x[i] ^ mask) - mask);
+            y2[i] += (y[i] ^ mask) - mask;
+        }
+        scrub(slice::from_mut(&mut mask));
+
+        // accept with probability 1 / (M exp(-|Sc|^2 / (2 sigma^2)) cosh(<z, Sc> / sigma^2)),
+        // where M = exp(PMAX / (2 sigma^2))
+        let norm = vecscalar(x.as_ref(), x.as_ref()) + vecscalar(y.as_ref(), y.as_ref());
+        if norm > V::PMAX { return false };
+        if !bernoulli_exp::<P, _>(rng, (V::PMAX - norm) as u32) { return false };
+        let scalar = vecscalar(sign.t.as_ref(), x.as_ref()) + vecscalar(y2.as_ref(), y.as_ref());
+        if !bernoulli_cosh::<P, _>(rng, scalar) { return false };
+
+        for i in 0..n {
+            let mut tmp = v[i] - y2[i];
+            if tmp < 0 { tmp += 2 * q };
+            if tmp >= 2 * q { tmp -= 2 * q };
+
+            tmp = ((tmp + (1 << (d - 1))) >> d) % p;
+
+            tmp = z[i] - tmp;
+            if tmp < -p / 2 { tmp += p };
+            if tmp > p / 2 { tmp -= p };
+            sign.z[i] = tmp;
+        }
+
+        // rarely the signature is too long for the verifier, start over
+        check_norms::<P, V>(sign.t.as_ref(), sign.z.as_ref())
+    }
+
+    pub fn export(&self) -> Result<P::PrivateKeyBytes, Error> {
+        let mut output = P::PrivateKeyBytes::zero();
 
         {
-            let mut bitpack = BitPack::<&mut [u8]>::new(&mut output);
-            for i in 0..N {
-                bitpack.write((self.f[i] + (1 << (F_BITS - 1))) as u32, F_BITS)?;
-                bitpack.write((self.g[i] + (1 << (G_BITS - 1))) as u32, G_BITS)?;
-                bitpack.write(self.a[i] as u32, A_BITS)?;
+            let mut bitpack = BitPack::<&mut [u8]>::new(output.as_mut());
+            for i in 0..P::N {
+                write(&mut bitpack, self.f[i] + (1 << (P::F_BITS - 1)), P::F_BITS, "f", i)?;
+                write(&mut bitpack, self.g[i] + (1 << (P::G_BITS - 1)), P::G_BITS, "g", i)?;
+                write(&mut bitpack, self.a[i], P::A_BITS, "a", i)?;
             }
         }
 
         Ok(output)
     }
 
-    pub fn import(input: &[u8; PRIVATEKEY_LENGTH]) -> Result<PrivateKey, usize> {
-        let mut privkey = PrivateKey {
-            f: [0; N],
-            g: [0; N],
-            a: [0; N]
-        };
+    pub fn import(input: &P::PrivateKeyBytes) -> Result<PrivateKey<P, V>, Error> {
+        let mut privkey = PrivateKey::<P, V>::zero();
 
         {
-            let mut bitpack = BitPack::<&[u8]>::new(input);
-            for i in 0..N {
-                privkey.f[i] = bitpack.read(F_BITS)? as i32 - (1 << (F_BITS - 1));
-                privkey.g[i] = bitpack.read(G_BITS)? as i32 - (1 << (G_BITS - 1));
-                privkey.a[i] = bitpack.read(A_BITS)? as i32;
+            let mut bitpack = BitPack::<&[u8]>::new(input.as_ref());
+            for i in 0..P::N {
+                privkey.f[i] = read(&mut bitpack, P::F_BITS, "f", i)? - (1 << (P::F_BITS - 1));
+                privkey.g[i] = read(&mut bitpack, P::G_BITS, "g", i)? - (1 << (P::G_BITS - 1));
+                privkey.a[i] = read(&mut bitpack, P::A_BITS, "a", i)?;
             }
         }
 
+        privkey.index();
         Ok(privkey)
     }
-}
-
 
-impl PublicKey {
-    pub fn verify(&self, sign: &Signature, hash: &[u8]) -> bool {
-        if vecabsmax(&sign.t) > B_INF || (vecabsmax(&sign.z) << D) > B_INF {
-            return false;
-        }
-        if vecscalar(&sign.t, &sign.t) + (vecscalar(&sign.z, &sign.z) << (2 * D)) > B_L2 {
-            return false;
-        }
+    /// Import and `validate`.
+    pub fn import_checked(input: &P::PrivateKeyBytes) -> Result<PrivateKey<P, V>, Error> {
+        let privkey = PrivateKey::<P, V>::import(input)?;
+        privkey.validate()?;
+        Ok(privkey)
+    }
 
-        let (mut v, mut vv) = ([0; N], [0; N]);
-        let mut my_idx = [0; KAPPA];
+    /// Import `f` and `g` only, recomputing `a` instead of trusting the stored one.
+    pub fn import_recompute(input: &P::PrivateKeyBytes) -> Result<PrivateKey<P, V>, Error> {
+        let privkey = PrivateKey::<P, V>::import(input)?;
+        PrivateKey::from_fg(&privkey.f, &privkey.g)
+    }
 
-        xmu(&mut v, &sign.t, &W);
-        fft(&mut v);
-        xmu(&mut vv, &v, &self.a);
-        fft(&mut vv);
-        xmu(&mut v, &vv, &R);
-        flp(&mut v);
+    /// `FORMAT_FG` followed by `f` and `g`, `a` is recomputed on import.
+    pub fn export_fg(&self) -> Result<P::PrivateKeyFgBytes, Error> {
+        let mut output = P::PrivateKeyFgBytes::zero();
+        output[0] = FORMAT_FG;
 
-        for i in 0..N {
-            if v[i] & 1 != 0 {
-                v[i] += Q;
+        {
+            let mut bitpack = BitPack::<&mut [u8]>::new(&mut output.as_mut()[1..]);
+            for i in 0..P::N {
+                write(&mut bitpack, self.f[i] + (1 << (P::F_BITS - 1)), P::F_BITS, "f", i)?;
+                write(&mut bitpack, self.g[i] + (1 << (P::G_BITS - 1)), P::G_BITS, "g", i)?;
             }
         }
 
-        for &i in sign.c_idx.iter() {
-            v[i] = (v[i] + Q) % (2 * Q);
-        }
+        Ok(output)
+    }
+
+    /// `FORMAT_SEED` followed by the seed, only for keys from `from_seed`.
+    pub fn export_seed(&self) -> Option<[u8; PRIVATEKEY_SEED_LENGTH]> {
+        self.seed.map(|seed| {
+            let mut output = [0; PRIVATEKEY_SEED_LENGTH];
+            output[0] = FORMAT_SEED;
+            output[1..].copy_from_slice(&seed);
+            output
+        })
+    }
 
-        for i in 0..N {
-            let tmp = (((v[i] + (1 << (D - 1))) >> D) + sign.z[i]) % P;
-            v[i] = if tmp < 0 { tmp + P } else { tmp };
+    /// Import any of `export`, `export_fg` or `export_seed`.
+    /// The untagged layout of `export` is told apart by its length.
+    pub fn import_any(input: &[u8]) -> Result<PrivateKey<P, V>, Error> {
+        if input.len() == P::PRIVATEKEY_LENGTH {
+            let mut buf = P::PrivateKeyBytes::zero();
+            buf.as_mut().copy_from_slice(input);
+            let result = PrivateKey::import(&buf);
+            scrub(buf.as_mut());
+            return result;
         }
 
-        if !c_oracle(&mut my_idx, hash, &v) {
-            return false;
+        let (expected, found) = match input.first() {
+            Some(&FORMAT_FG) => (P::PRIVATEKEY_FG_LENGTH, input.len()),
+            Some(&FORMAT_SEED) => (PRIVATEKEY_SEED_LENGTH, input.len()),
+            _ => return Err(Error::Encoding { field: "format", index: 0 })
+        };
+        if expected != found {
+            return Err(Error::Length { expected, found });
         }
 
-        let mut d = 0;
-        for i in 0..KAPPA {
-            d |= my_idx[i] ^ sign.c_idx[i];
+        if input[0] == FORMAT_SEED {
+            let mut seed = [0; 32];
+            seed.copy_from_slice(&input[1..]);
+            let result = PrivateKey::from_seed(&seed);
+            scrub(&mut seed);
+            return result;
         }
-        d == 0
+
+        let (mut f, mut g) = (P::Poly::zero(), P::Poly::zero());
+        let result = (|| {
+            let mut bitpack = BitPack::<&[u8]>::new(&input[1..]);
+            for i in 0..P::N {
+                f[i] = read(&mut bitpack, P::F_BITS, "f", i)? - (1 << (P::F_BITS - 1));
+                g[i] = read(&mut bitpack, P::G_BITS, "g", i)? - (1 << (P::G_BITS - 1));
+            }
+            PrivateKey::from_fg(&f, &g)
+        })();
+        scrub(f.as_mut());
+        scrub(g.as_mut());
+        result
+    }
+}
+
+
+impl<P: ParameterSet, V: Variant<P>> PublicKey<P, V> {
+    pub fn verify(&self, sign: &Signature<P, V>, hash: &[u8]) -> bool {
+        self.prepare().verify(sign, hash)
     }
 
-    pub fn export(&self) -> Result<[u8; PUBLICKEY_LENGTH], usize> {
-        let mut output = [0; PUBLICKEY_LENGTH];
+    /// Cache `a` for verifying many signatures.
+    pub fn prepare(&self) -> PreparedPublicKey<P, V> {
+        let mut a = P::Poly::zero();
+        prepare::<P>(a.as_mut(), self.a.as_ref());
+        PreparedPublicKey { a, variant: PhantomData }
+    }
+
+    pub fn export(&self) -> Result<P::PublicKeyBytes, Error> {
+        let mut output = P::PublicKeyBytes::zero();
 
         {
-            let mut bitpack = BitPack::<&mut [u8]>::new(&mut output);
-            for &b in &self.a[..] {
-                bitpack.write(b as u32, A_BITS)?;
+            let mut bitpack = BitPack::<&mut [u8]>::new(output.as_mut());
+            for i in 0..P::N {
+                write(&mut bitpack, self.a[i], P::A_BITS, "a", i)?;
             }
         }
 
         Ok(output)
     }
 
-    pub fn import(input: &[u8; PUBLICKEY_LENGTH]) -> Result<PublicKey, usize> {
-        let mut pubkey = PublicKey {
-            a: [0; N]
+    pub fn import(input: &P::PublicKeyBytes) -> Result<PublicKey<P, V>, Error> {
+        let mut pubkey = PublicKey::<P, V> {
+            a: P::Poly::zero(),
+            variant: PhantomData
         };
 
         {
-            let mut bitpack = BitPack::<&[u8]>::new(input);
-            for i in 0..N {
-                pubkey.a[i] = bitpack.read(A_BITS)? as i32;
+            let mut bitpack = BitPack::<&[u8]>::new(input.as_ref());
+            for i in 0..P::N {
+                pubkey.a[i] = read(&mut bitpack, P::A_BITS, "a", i)?;
+                if pubkey.a[i] >= P::Q {
+                    return Err(Error::Encoding { field: "a", index: i });
+                }
             }
         }
 
@@ -274,39 +822,352 @@ impl PublicKey {
     }
 }
 
-impl Signature {
-    pub fn export(&self) -> Result<[u8; SIGNATURE_LENGTH], usize> {
-        let mut output = [0; SIGNATURE_LENGTH];
+impl<P: ParameterSet, V: Variant<P>> PreparedPublicKey<P, V> {
+    pub fn verify(&self, sign: &Signature<P, V>, hash: &[u8]) -> bool {
+        verify::<P, V>(&self.a, sign, hash)
+    }
+}
+
+impl<P: ParameterSet, V: Variant<P>> PreparedPrivateKey<P, V> {
+    pub fn key(&self) -> &PrivateKey<P, V> {
+        &self.key
+    }
+
+    pub fn public(&self) -> PreparedPublicKey<P, V> {
+        PreparedPublicKey {
+            a: self.a,
+            variant: PhantomData
+        }
+    }
+
+    pub fn sign<R: CryptoRng>(&self, rng: &mut R, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        self.key.sign_rng(&self.a, &self.sampler, rng, hash, &mut Scratch::new())
+    }
+
+    pub fn sign_with<R: CryptoRng, S: GaussianSampler<P>>(&self, sampler: &S, rng: &mut R, hash: &[u8])
+        -> Result<Signature<P, V>, Error>
+    {
+        self.key.sign_rng(&self.a, sampler, rng, hash, &mut Scratch::new())
+    }
+
+    /// See `PrivateKey::sign_deterministic`, the signatures are the same.
+    pub fn sign_deterministic(&self, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        self.key.sign_prf_with(&self.a, &self.prf_key, &self.sampler, &[], hash, &mut Scratch::new())
+    }
+
+    pub fn sign_hedged<R: CryptoRng>(&self, rng: &mut R, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        hedged(rng, |extra| self.key.sign_prf_with(&self.a, &self.prf_key, &self.sampler, extra, hash, &mut Scratch::new()))
+    }
+
+    pub fn sign_prf<S: GaussianSampler<P>>(&self, sampler: &S, extra: &[u8], hash: &[u8])
+        -> Result<Signature<P, V>, Error>
+    {
+        self.key.sign_prf_with(&self.a, &self.prf_key, sampler, extra, hash, &mut Scratch::new())
+    }
+}
+
+impl<P: ParameterSet, V: Variant<P>> From<PrivateKey<P, V>> for PreparedPrivateKey<P, V> {
+    fn from(key: PrivateKey<P, V>) -> PreparedPrivateKey<P, V> {
+        key.prepare()
+    }
+}
+
+impl<P: ParameterSet, R: CryptoRng, V: Variant<P>> Signer<P, R, V> {
+    /// Stack needed by `sign`, `sign_deterministic` and `sign_hedged`, including their callees.
+    pub const STACK_BUDGET: usize = STACK_BASE + STACK_POLYS * 4 * P::N;
+
+    pub fn new<K: Into<PreparedPrivateKey<P, V>>>(key: K, rng: R) -> Signer<P, R, V> {
+        Signer {
+            key: key.into(),
+            rng,
+            scratch: Box::new(Scratch::new())
+        }
+    }
+
+    pub fn key(&self) -> &PreparedPrivateKey<P, V> {
+        &self.key
+    }
+
+    pub fn rng(&mut self) -> &mut R {
+        &mut self.rng
+    }
+
+    pub fn sign(&mut self, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        let Signer { ref key, ref mut rng, ref mut scratch } = *self;
+        key.key.sign_rng(&key.a, &key.sampler, rng, hash, scratch)
+    }
+
+    /// See `PrivateKey::sign_deterministic`, the signatures are the same.
+    pub fn sign_deterministic(&mut self, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        let Signer { ref key, ref mut scratch, .. } = *self;
+        key.key.sign_prf_with(&key.a, &key.prf_key, &key.sampler, &[], hash, scratch)
+    }
+
+    /// See `PrivateKey::sign_hedged`, with the extra randomness drawn from the signer's RNG.
+    pub fn sign_hedged(&mut self, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        let Signer { ref key, ref mut rng, ref mut scratch } = *self;
+        hedged(rng, |extra| key.key.sign_prf_with(&key.a, &key.prf_key, &key.sampler, extra, hash, scratch))
+    }
+}
+
+impl<P: ParameterSet, R: CryptoRng, V: Variant<P>> fmt::Debug for Signer<P, R, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Signer")
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+impl<P: ParameterSet, R: CryptoRng, V: Variant<P>> OnlineSigner<P, R, V> {
+    /// A signer with room for `capacity` commitments, initially none.
+    pub fn new<K: Into<PreparedPrivateKey<P, V>>>(key: K, rng: R, capacity: usize) -> OnlineSigner<P, R, V> {
+        OnlineSigner {
+            key: Arc::new(key.into()),
+            pool: Arc::new(Pool {
+                entries: Mutex::new(Vec::with_capacity(capacity)),
+                capacity,
+                taken: Condvar::new(),
+                stop: AtomicBool::new(false)
+            }),
+            rng,
+            scratch: Box::new(Scratch::new()),
+            worker: None
+        }
+    }
+
+    pub fn key(&self) -> &PreparedPrivateKey<P, V> {
+        &self.key
+    }
+
+    /// Commitments ready for signing.
+    pub fn len(&self) -> usize {
+        self.pool.entries.lock().unwrap().len()
+    }
+
+    /// No commitment ready, the next `sign` computes its own.
+    pub fn is_empty(&self) -> bool {
+        self.pool.entries.lock().unwrap().is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.pool.capacity
+    }
+
+    /// The offline phase on this thread, fill the pool with the signer's RNG
+    /// and return the number of commitments added.
+    pub fn precompute(&mut self) -> usize {
+        let mut added = 0;
+        while self.len() < self.pool.capacity {
+            let mut entry = Box::new(Commitment::zero());
+            entry.fill(&self.key.a, &self.key.sampler, &mut self.rng);
+            if self.pool.push(entry).is_some() { break };
+            added += 1;
+        }
+        added
+    }
+
+    /// Keep the pool full from a background thread drawing from `rng`,
+    /// until the signer is dropped.
+    pub fn background<Q: CryptoRng + Send + 'static>(mut self, mut rng: Q) -> OnlineSigner<P, R, V> {
+        assert!(self.worker.is_none(), "background precomputation already running");
+        let (key, pool) = (self.key.clone(), self.pool.clone());
+        self.worker = Some(thread::spawn(move || {
+            while pool.wait_room() {
+                let mut entry = Box::new(Commitment::zero());
+                entry.fill(&key.a, &key.sampler, &mut rng);
+                pool.push(entry);
+            }
+        }));
+        self
+    }
+
+    /// The online phase, every attempt spends a commitment of the pool,
+    /// or one computed now if the pool is empty.
+    pub fn sign(&mut self, hash: &[u8]) -> Result<Signature<P, V>, Error> {
+        let OnlineSigner { ref key, ref pool, ref mut rng, ref mut scratch, .. } = *self;
+        let Scratch { ref mut com, ref mut x, ref mut y, ref mut sign } = **scratch;
+
+        for _ in 0..1024 {
+            let accepted = match pool.take() {
+                Some(mut entry) => key.key.respond(&mut entry, rng, hash, x, y, sign),
+                None => {
+                    com.fill(&key.a, &key.sampler, rng);
+                    key.key.respond(com, rng, hash, x, y, sign)
+                }
+            };
+            if accepted {
+                return Ok(mem::replace(sign, Signature::zero()));
+            }
+        }
+
+        Err(Error::SignExhausted)
+    }
+}
+
+impl<P: ParameterSet, R: CryptoRng, V: Variant<P>> Drop for OnlineSigner<P, R, V> {
+    fn drop(&mut self) {
+        if let Some(worker) = self.worker.take() {
+            self.pool.stop.store(true, Ordering::SeqCst);
+            // with the lock taken once, the worker is either waiting or yet to see `stop`
+            drop(self.pool.entries.lock().unwrap());
+            self.pool.taken.notify_all();
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<P: ParameterSet, R: CryptoRng, V: Variant<P>> fmt::Debug for OnlineSigner<P, R, V> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OnlineSigner")
+            .field("key", &self.key)
+            .field("len", &self.len())
+            .field("capacity", &self.pool.capacity)
+            .finish()
+    }
+}
+
+/// A public key, a signature and the hash it should sign.
+pub type BatchItem<P, V, H> = (PublicKey<P, V>, Signature<P, V>, H);
+
+/// Verify every signature of `items` on all cores, see `verify_batch_threads`.
+pub fn verify_batch<P, V, H>(items: &[BatchItem<P, V, H>]) -> Vec<usize>
+    where P: ParameterSet, V: Variant<P>, H: AsRef<[u8]> + Sync
+{
+    verify_batch_threads(items, cores())
+}
+
+/// Verify every signature of `items` against its key and hash on up to `threads` threads,
+/// return the indices of those that fail.
+pub fn verify_batch_threads<P, V, H>(items: &[BatchItem<P, V, H>], threads: usize) -> Vec<usize>
+    where P: ParameterSet, V: Variant<P>, H: AsRef<[u8]> + Sync
+{
+    let mut valid = vec![false; items.len()];
+    let _ = parallel(items, &mut valid, threads, |items, valid| {
+        for ((pubkey, sign, hash), valid) in items.iter().zip(valid) {
+            *valid = pubkey.verify(sign, hash.as_ref());
+        }
+        Ok(())
+    });
+
+    (0..items.len()).filter(|&i| !valid[i]).collect()
+}
+
+/// Sign every hash of `hashes` on all cores, see `sign_batch_threads`.
+pub fn sign_batch<P, V, H>(key: &PrivateKey<P, V>, hashes: &[H]) -> Result<Vec<Signature<P, V>>, Error>
+    where P: ParameterSet, V: Variant<P>, H: AsRef<[u8]> + Sync
+{
+    sign_batch_threads(key, hashes, cores())
+}
+
+/// Sign every hash of `hashes` on up to `threads` threads, each with its own scratch buffers
+/// and a `ChaChaRng` seeded from `OsRng`, sharing `a` and the sampler tables.
+pub fn sign_batch_threads<P, V, H>(key: &PrivateKey<P, V>, hashes: &[H], threads: usize)
+    -> Result<Vec<Signature<P, V>>, Error>
+    where P: ParameterSet, V: Variant<P>, H: AsRef<[u8]> + Sync
+{
+    let (a, sampler) = (key.prepare_a(), DefaultSampler::<P>::default());
+    let mut signs = (0..hashes.len()).map(|_| Signature::zero()).collect::<Vec<_>>();
+
+    parallel(hashes, &mut signs, threads, |hashes, signs| {
+        let mut rng = OsRng::new()?.gen::<ChaChaRng>();
+        let mut scratch = Box::new(Scratch::new());
+        for (hash, sign) in hashes.iter().zip(signs) {
+            *sign = key.sign_rng(&a, &sampler, &mut rng, hash.as_ref(), &mut scratch)?;
+        }
+        Ok(())
+    })?;
+
+    Ok(signs)
+}
+
+fn cores() -> usize {
+    thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+}
+
+/// Run `f` over matching chunks of `input` and `output` on up to `threads` scoped threads,
+/// the calling thread takes the first chunk.
+fn parallel<T, U, F>(input: &[T], output: &mut [U], threads: usize, f: F) -> Result<(), Error>
+    where T: Sync, U: Send, F: Fn(&[T], &mut [U]) -> Result<(), Error> + Sync
+{
+    let len = input.len();
+    if len == 0 { return Ok(()) };
+    let chunk = len.div_ceil(threads.max(1));
+
+    thread::scope(|scope| {
+        let f = &f;
+        let mut chunks = input.chunks(chunk).zip(output.chunks_mut(chunk));
+        let first = chunks.next();
+        let workers = chunks
+            .map(|(input, output)| scope.spawn(move || f(input, output)))
+            .collect::<Vec<_>>();
+
+        let mut result = first.map_or(Ok(()), |(input, output)| f(input, output));
+        for worker in workers {
+            let r = worker.join().unwrap();
+            if result.is_ok() { result = r };
+        }
+        result
+    })
+}
+
+impl<P: ParameterSet, V: Variant<P>> Signature<P, V> {
+    fn zero() -> Signature<P, V> {
+        Signature {
+            t: P::Poly::zero(),
+            z: P::Poly::zero(),
+            c_idx: P::Index::zero(),
+            variant: PhantomData
+        }
+    }
+
+    pub fn export(&self) -> Result<P::SignatureBytes, Error> {
+        let mut output = P::SignatureBytes::zero();
 
         {
-            let mut bitpack = BitPack::<&mut [u8]>::new(&mut output);
-            for i in 0..N {
-                bitpack.write((self.t[i] + (1 << (T_BITS - 1))) as u32, T_BITS)?;
-                bitpack.write((self.z[i] + (1 << (Z_BITS - 1))) as u32, Z_BITS)?;
+            let mut bitpack = BitPack::<&mut [u8]>::new(output.as_mut());
+            for i in 0..P::N {
+                write(&mut bitpack, self.t[i] + (1 << (P::T_BITS - 1)), P::T_BITS, "t", i)?;
+                write(&mut bitpack, self.z[i] + (1 << (P::Z_BITS - 1)), P::Z_BITS, "z", i)?;
             }
-            for i in 0..KAPPA {
-                bitpack.write(self.c_idx[i] as u32, CIDX_BITS)?;
+            for i in 0..P::KAPPA {
+                write(&mut bitpack, self.c_idx[i] as i32, P::CIDX_BITS, "c_idx", i)?;
             }
         }
 
         Ok(output)
     }
 
-    pub fn import(input: &[u8]) -> Result<Signature, usize> {
-        let mut sign = Signature {
-            t: [0; N],
-            z: [0; N],
-            c_idx: [0; KAPPA]
-        };
+    /// Only the canonical encoding is accepted: coefficients within the verification bounds,
+    /// `c_idx` strictly increasing and zero padding, so a signature has exactly one encoding.
+    pub fn import(input: &[u8]) -> Result<Signature<P, V>, Error> {
+        let mut sign = Signature::<P, V>::zero();
+
+        if input.len() != P::SIGNATURE_LENGTH {
+            return Err(Error::Length { expected: P::SIGNATURE_LENGTH, found: input.len() });
+        }
 
         {
             let mut bitpack = BitPack::<&[u8]>::new(input);
-            for i in 0..N {
-                sign.t[i] = bitpack.read(T_BITS)? as i32 - (1 << (T_BITS - 1));
-                sign.z[i] = bitpack.read(Z_BITS)? as i32 - (1 << (Z_BITS - 1));
+            for i in 0..P::N {
+                sign.t[i] = read(&mut bitpack, P::T_BITS, "t", i)? - (1 << (P::T_BITS - 1));
+                if sign.t[i].abs() > P::B_INF {
+                    return Err(Error::Encoding { field: "t", index: i });
+                }
+                sign.z[i] = read(&mut bitpack, P::Z_BITS, "z", i)? - (1 << (P::Z_BITS - 1));
+                if sign.z[i].abs() << P::D > P::B_INF {
+                    return Err(Error::Encoding { field: "z", index: i });
+                }
+            }
+            for i in 0..P::KAPPA {
+                sign.c_idx[i] = read(&mut bitpack, P::CIDX_BITS, "c_idx", i)? as usize;
+                if i > 0 && sign.c_idx[i] <= sign.c_idx[i - 1] {
+                    return Err(Error::Encoding { field: "c_idx", index: i });
+                }
             }
-            for i in 0..KAPPA {
-                sign.c_idx[i] = bitpack.read(CIDX_BITS)? as usize;
+
+            let padding = P::SIGNATURE_LENGTH * 8 - bitpack.sum_bits();
+            if padding > 0 && read(&mut bitpack, padding, "padding", 0)? != 0 {
+                return Err(Error::Encoding { field: "padding", index: 0 });
             }
         }
 
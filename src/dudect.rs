@@ -0,0 +1,53 @@
+//! Leakage checks for the constant-time code, in the spirit of dudect.
+//!
+//! The timing tests are ignored by default, run them with
+//! `cargo test --release --features ct -- --ignored`.
+
+use std::time::Instant;
+use rand::{ Rng, ChaChaRng };
+
+
+/// Counts the words drawn from `rng`, code without secret dependent branches
+/// draws the same number of words whatever the secret is.
+pub struct CountingRng<R> {
+    pub rng: R,
+    pub count: usize
+}
+
+impl<R: Rng> Rng for CountingRng<R> {
+    fn next_u32(&mut self) -> u32 {
+        self.count += 1;
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.count += 2;
+        self.rng.next_u64()
+    }
+}
+
+/// Welch's t statistic between the running times of `f(false)` and `f(true)`,
+/// with the class picked at random for every round.
+/// dudect considers `|t| > 10` a definite leak.
+pub fn leakage<F: FnMut(bool)>(rounds: usize, mut f: F) -> f64 {
+    let mut rng = ChaChaRng::new_unseeded();
+    let mut n = [0.0; 2];
+    let mut mean = [0.0; 2];
+    let mut m2 = [0.0; 2];
+
+    for _ in 0..rounds {
+        let class = rng.gen::<bool>();
+        let now = Instant::now();
+        f(class);
+        let t = now.elapsed().subsec_nanos() as f64;
+
+        let c = class as usize;
+        n[c] += 1.0;
+        let delta = t - mean[c];
+        mean[c] += delta / n[c];
+        m2[c] += delta * (t - mean[c]);
+    }
+
+    let var = [m2[0] / (n[0] - 1.0), m2[1] / (n[1] - 1.0)];
+    (mean[0] - mean[1]) / (var[0] / n[0] + var[1] / n[1]).sqrt()
+}
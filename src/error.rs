@@ -0,0 +1,46 @@
+use std::{ io, fmt, error };
+
+
+#[derive(Debug)]
+pub enum Error {
+    /// The operating system random number generator failed.
+    Rng(io::Error),
+    /// Key generation found no invertible `f` within the retry budget.
+    KeygenExhausted,
+    /// Signing passed no rejection step within the retry budget.
+    SignExhausted,
+    /// A coefficient is out of range for its field, or the input is truncated.
+    Encoding { field: &'static str, index: usize },
+    /// The input has the wrong length.
+    Length { expected: usize, found: usize },
+    /// A key or signature belongs to another parameter set.
+    ParameterMismatch
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Rng(err)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Rng(ref err) => write!(f, "random number generator failed: {}", err),
+            Error::KeygenExhausted => f.write_str("unable to generate the correct private key"),
+            Error::SignExhausted => f.write_str("unable to generate the correct signature"),
+            Error::Encoding { field, index } => write!(f, "malformed encoding of {}[{}]", field, index),
+            Error::Length { expected, found } => write!(f, "expected {} bytes, found {}", expected, found),
+            Error::ParameterMismatch => f.write_str("parameter set mismatch")
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Rng(ref err) => Some(err),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::marker::PhantomData;
+use rand::Rng;
+use ::param::ParameterSet;
+
+
+/// Sampler for the discrete Gaussian `D_{sigma}` with `sigma = P::K_SIGMA * sqrt(1 / (2 ln 2))`.
+pub trait GaussianSampler<P: ParameterSet> {
+    fn sample<R: Rng>(&self, rng: &mut R) -> i32;
+}
+
+/// The sampler used by `PrivateKey::signature`.
+#[cfg(not(feature = "ct"))]
+pub type DefaultSampler<P> = BernoulliSampler<P>;
+
+/// The sampler used by `PrivateKey::signature`, the Bernoulli sampler
+/// leaks its output through the number of restarts.
+#[cfg(feature = "ct")]
+pub type DefaultSampler<P> = CdtSampler<P>;
+
+/// Bernoulli trial with probability `exp(-x / (2 sigma^2))`.
+#[cfg(not(feature = "ct"))]
+pub fn bernoulli_exp<P: ParameterSet, R: Rng>(rng: &mut R, mut x: u32) -> bool {
+    if x >> P::EXP_TABLE.len() != 0 { return false };
+
+    let mut i = 0;
+    while x != 0 {
+        if x & 1 != 0 && rng.next_u64() >= P::EXP_TABLE[i] {
+            return false;
+        }
+        x >>= 1;
+        i += 1;
+    }
+
+    true
+}
+
+/// Bernoulli trial with probability `exp(-x / (2 sigma^2))`,
+/// drawing one word for every row of the table whatever `x` is.
+#[cfg(feature = "ct")]
+pub fn bernoulli_exp<P: ParameterSet, R: Rng>(rng: &mut R, x: u32) -> bool {
+    let mut ok = (x >> P::EXP_TABLE.len() == 0) as u64;
+
+    for (i, &e) in P::EXP_TABLE.iter().enumerate() {
+        let bit = (x >> i) as u64 & 1;
+        let lt = ((rng.next_u64() as u128).wrapping_sub(e as u128) >> 127) as u64;
+        ok &= lt | (bit ^ 1);
+    }
+
+    ok != 0
+}
+
+/// Bernoulli trial with probability `1 / cosh(x / sigma^2)`.
+#[cfg(not(feature = "ct"))]
+pub fn bernoulli_cosh<P: ParameterSet, R: Rng>(rng: &mut R, x: i32) -> bool {
+    let x = x.unsigned_abs().saturating_mul(2);
+
+    loop {
+        if bernoulli_exp::<P, _>(rng, x) { return true };
+        if rng.next_u32() & 1 != 0 { continue };
+        if !bernoulli_exp::<P, _>(rng, x) { return false };
+    }
+}
+
+/// Bernoulli trial with probability `1 / cosh(x / sigma^2)`, always running `COSH_ROUNDS` rounds.
+/// A round stays undecided with probability at most 1/2, so the bias is below `2^-COSH_ROUNDS`.
+#[cfg(feature = "ct")]
+pub fn bernoulli_cosh<P: ParameterSet, R: Rng>(rng: &mut R, x: i32) -> bool {
+    const COSH_ROUNDS: usize = 64;
+
+    let x = x.unsigned_abs().saturating_mul(2);
+    let (mut done, mut accept) = (0, 0);
+
+    for _ in 0..COSH_ROUNDS {
+        let a = bernoulli_exp::<P, _>(rng, x) as u32;
+        let b = rng.next_u32() & 1;
+        let c = bernoulli_exp::<P, _>(rng, x) as u32;
+
+        // a: accept, otherwise b: restart, otherwise !c: reject
+        accept |= !done & a;
+        done |= a | (!b & !c & 1);
+    }
+
+    accept & 1 != 0
+}
+
+/// `rho(x) = exp(-x^2 / (2 sigma^2))` as a fixed point number with 64 fractional bits.
+fn rho<P: ParameterSet>(x: u32) -> u128 {
+    let mut e = x * x;
+    if e >> P::EXP_TABLE.len() != 0 { return 0 };
+
+    let mut y = 1 << 64;
+    let mut i = 0;
+    while e != 0 {
+        if e & 1 != 0 {
+            y = (y * P::EXP_TABLE[i] as u128) >> 64;
+        }
+        e >>= 1;
+        i += 1;
+    }
+    y
+}
+
+/// Probabilities of `|x|` with 64 fractional bits, up to the tail cut.
+/// They are rounded down, so that they sum to at most one.
+fn probabilities<P: ParameterSet>() -> Vec<u64> {
+    // rho(0) for zero and 2 rho(x) for the rest
+    let mut weights = vec![rho::<P>(0)];
+    for x in 1.. {
+        let y = rho::<P>(x);
+        if y == 0 { break };
+        weights.push(2 * y);
+    }
+
+    let total = weights.iter().sum::<u128>();
+    weights.iter()
+        .map(|&w| ((w << 52) / ((total >> 12) + 1)) as u64)
+        .collect()
+}
+
+/// Uniform `y < k` by rejection.
+fn uniform<R: Rng>(rng: &mut R, k: u32) -> u32 {
+    let mask = k.next_power_of_two() - 1;
+    loop {
+        let y = rng.next_u32() & mask;
+        if y < k { return y };
+    }
+}
+
+#[inline]
+fn signed<R: Rng>(rng: &mut R, x: u32) -> i32 {
+    let mask = -((rng.next_u32() & 1) as i32);
+    (x as i32 ^ mask) - mask
+}
+
+
+/// The sampler of the BLISS paper: a binary Gaussian scaled by `P::K_SIGMA`
+/// and corrected with Bernoulli trials over `P::EXP_TABLE`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BernoulliSampler<P> {
+    params: PhantomData<P>
+}
+
+impl<P: ParameterSet> BernoulliSampler<P> {
+    pub fn new() -> BernoulliSampler<P> {
+        BernoulliSampler { params: PhantomData }
+    }
+
+    /// Sample from the binary Gaussian `D+_{sigma_2}`, where `rho(x) = 2^(-x^2)`.
+    fn binary_gauss<R: Rng>(rng: &mut R) -> u32 {
+        'restart: loop {
+            if rng.next_u32() & 1 == 0 { return 0 };
+
+            let mut i = 1;
+            loop {
+                // draw 2i - 1 bits, all but the last must be zero
+                for _ in 0..(2 * i - 2) {
+                    if rng.next_u32() & 1 != 0 { continue 'restart };
+                }
+                if rng.next_u32() & 1 == 0 { return i };
+                i += 1;
+            }
+        }
+    }
+}
+
+impl<P: ParameterSet> GaussianSampler<P> for BernoulliSampler<P> {
+    fn sample<R: Rng>(&self, rng: &mut R) -> i32 {
+        let k = P::K_SIGMA as u32;
+
+        loop {
+            let x = BernoulliSampler::<P>::binary_gauss(rng);
+            let y = uniform(rng, k);
+
+            if !bernoulli_exp::<P, _>(rng, y * (y + 2 * k * x)) { continue };
+
+            let z = k * x + y;
+            if z == 0 && rng.next_u32() & 1 == 0 { continue };
+
+            return signed(rng, z);
+        }
+    }
+}
+
+
+/// Cumulative distribution table sampler, a binary search over the table,
+/// or a scan over the whole table with the `ct` feature.
+#[derive(Clone, Debug)]
+pub struct CdtSampler<P> {
+    table: Vec<u64>,
+    params: PhantomData<P>
+}
+
+impl<P: ParameterSet> CdtSampler<P> {
+    pub fn new() -> CdtSampler<P> {
+        let mut table = probabilities::<P>();
+        for i in 1..table.len() {
+            table[i] += table[i - 1];
+        }
+        CdtSampler { table, params: PhantomData }
+    }
+}
+
+impl<P: ParameterSet> Default for CdtSampler<P> {
+    fn default() -> CdtSampler<P> {
+        CdtSampler::new()
+    }
+}
+
+impl<P: ParameterSet> GaussianSampler<P> for CdtSampler<P> {
+    #[cfg(not(feature = "ct"))]
+    fn sample<R: Rng>(&self, rng: &mut R) -> i32 {
+        loop {
+            let r = rng.next_u64();
+            let x = self.table.partition_point(|&c| c <= r);
+
+            // r fell into the rounding loss of the table
+            if x == self.table.len() { continue };
+
+            return signed(rng, x as u32);
+        }
+    }
+
+    #[cfg(feature = "ct")]
+    fn sample<R: Rng>(&self, rng: &mut R) -> i32 {
+        loop {
+            let r = rng.next_u64() as u128;
+            let mut x = 0;
+            for &c in &self.table {
+                x += (c as u128).wrapping_sub(r + 1) >> 127;
+            }
+
+            if x as usize == self.table.len() { continue };
+
+            return signed(rng, x as u32);
+        }
+    }
+}
+
+
+/// Knuth-Yao sampler, a random walk down the discrete distribution generating tree
+/// given by the binary expansions of the probabilities.
+#[derive(Clone, Debug)]
+pub struct KnuthYaoSampler<P> {
+    probs: Vec<u64>,
+    hamming: Vec<u64>,
+    params: PhantomData<P>
+}
+
+impl<P: ParameterSet> KnuthYaoSampler<P> {
+    pub fn new() -> KnuthYaoSampler<P> {
+        let probs = probabilities::<P>();
+
+        // column c holds the bit of weight 2^-(c + 1)
+        let hamming = (0..64)
+            .map(|c| probs.iter()
+                .filter(|&p| (p >> (63 - c)) & 1 != 0)
+                .count() as u64
+            )
+            .collect();
+
+        KnuthYaoSampler { probs, hamming, params: PhantomData }
+    }
+}
+
+impl<P: ParameterSet> Default for KnuthYaoSampler<P> {
+    fn default() -> KnuthYaoSampler<P> {
+        KnuthYaoSampler::new()
+    }
+}
+
+impl<P: ParameterSet> GaussianSampler<P> for KnuthYaoSampler<P> {
+    fn sample<R: Rng>(&self, rng: &mut R) -> i32 {
+        loop {
+            let mut d = 0;
+            for c in 0..64 {
+                d = 2 * d + (rng.next_u32() & 1) as u64;
+
+                if d < self.hamming[c] {
+                    for (x, &p) in self.probs.iter().enumerate() {
+                        if (p >> (63 - c)) & 1 != 0 {
+                            if d == 0 { return signed(rng, x as u32) };
+                            d -= 1;
+                        }
+                    }
+                }
+                d -= self.hamming[c];
+            }
+
+            // fell off the tree, into the rounding loss of the probabilities
+        }
+    }
+}
+
+
+#[cfg(test)]
+fn check_distribution<P: ParameterSet, S: GaussianSampler<P>>(sampler: &S) {
+    use rand::{ ChaChaRng, SeedableRng };
+
+    const SAMPLES: usize = 1 << 18;
+
+    let mut rng = ChaChaRng::from_seed(&[P::K_SIGMA as u32]);
+    let k = P::K_SIGMA as f64;
+    let tail = 13 * P::K_SIGMA as usize;
+
+    // rho(x) = exp(-x^2 / (2 sigma^2)) = 2^(-x^2 / K_SIGMA^2)
+    let rho = |x: usize| (-((x * x) as f64) / (k * k)).exp2();
+    let total = (1..tail).fold(rho(0), |sum, x| sum + 2.0 * rho(x));
+
+    let mut hist = vec![0usize; tail];
+    let (mut pos, mut neg) = (0, 0);
+    for _ in 0..SAMPLES {
+        let x = sampler.sample(&mut rng);
+        assert!((x.unsigned_abs() as usize) < tail);
+        hist[x.unsigned_abs() as usize] += 1;
+        if x > 0 { pos += 1 };
+        if x < 0 { neg += 1 };
+    }
+
+    // both signs are equally likely
+    let diff = (pos as f64 - neg as f64).abs();
+    assert!(diff < 5.0 * ((pos + neg) as f64).sqrt(), "{} {}", pos, neg);
+
+    // chi-squared goodness of fit of |x|, merging buckets to at least 64 expected hits
+    let (mut chi2, mut df) = (0.0, 0);
+    let (mut expect, mut observe) = (0.0, 0.0);
+    for (x, &h) in hist.iter().enumerate() {
+        let p = if x == 0 { rho(0) } else { 2.0 * rho(x) } / total;
+        expect += p * SAMPLES as f64;
+        observe += h as f64;
+        if expect >= 64.0 || x == tail - 1 {
+            chi2 += (observe - expect) * (observe - expect) / expect;
+            df += 1;
+            expect = 0.0;
+            observe = 0.0;
+        }
+    }
+
+    // far beyond the 99.99% quantile of chi^2(df), which is below df + 4.5 sqrt(2 df) + 12
+    let bound = df as f64 + 6.0 * (2.0 * df as f64).sqrt() + 12.0;
+    assert!(chi2 < bound, "chi2 = {}, df = {}", chi2, df);
+}
+
+#[cfg(test)]
+fn check_bernoulli_trials<P: ParameterSet>() {
+    use rand::{ ChaChaRng, SeedableRng };
+
+    const TRIALS: usize = 1 << 16;
+
+    let mut rng = ChaChaRng::from_seed(&[P::K_SIGMA as u32]);
+    let sigma = P::K_SIGMA as f64 * (0.5 / 2f64.ln()).sqrt();
+    let count = |rng: &mut ChaChaRng, f: &dyn Fn(&mut ChaChaRng) -> bool|
+        (0..TRIALS).filter(|_| f(rng)).count() as f64 / TRIALS as f64;
+
+    // the tables are consistent with SIGMA and M = exp(PMAX / (2 sigma^2))
+    assert!((sigma / P::SIGMA - 1.0).abs() < 0.01);
+    assert!(((P::PMAX as f64 / (2.0 * sigma * sigma)).exp() / P::M - 1.0).abs() < 0.01);
+
+    for &x in &[0, 1, 1000, P::PMAX / 4, P::PMAX, 10 * P::PMAX] {
+        let p = (-x as f64 / (2.0 * sigma * sigma)).exp();
+        let q = count(&mut rng, &|rng| bernoulli_exp::<P, _>(rng, x as u32));
+        assert!((p - q).abs() < 0.01, "exp {}: {} {}", x, p, q);
+
+        let p = 1.0 / (x as f64 / (sigma * sigma)).cosh();
+        let q = count(&mut rng, &|rng| bernoulli_cosh::<P, _>(rng, -x));
+        assert!((p - q).abs() < 0.01, "cosh {}: {} {}", x, p, q);
+    }
+}
+
+#[test]
+fn test_bernoulli_trials() {
+    all_sets!(P => check_bernoulli_trials::<P>());
+}
+
+#[cfg(feature = "ct")]
+#[test]
+fn test_ct_rng_usage() {
+    use rand::{ ChaChaRng, SeedableRng };
+    use ::dudect::CountingRng;
+    use ::param::BlissI as P;
+
+    let mut rng = CountingRng { rng: ChaChaRng::from_seed(&[2]), count: 0 };
+    let count = |rng: &mut CountingRng<ChaChaRng>, f: &dyn Fn(&mut CountingRng<ChaChaRng>)| {
+        rng.count = 0;
+        f(rng);
+        rng.count
+    };
+
+    let xs = [0, 1, 1000, P::PMAX, -P::PMAX, 1 << 30];
+    let exp = xs.iter().map(|&x| count(&mut rng, &|rng| { bernoulli_exp::<P, _>(rng, x as u32); })).collect::<Vec<_>>();
+    let cosh = xs.iter().map(|&x| count(&mut rng, &|rng| { bernoulli_cosh::<P, _>(rng, x); })).collect::<Vec<_>>();
+    assert!(exp.iter().all(|&c| c == exp[0]), "{:?}", exp);
+    assert!(cosh.iter().all(|&c| c == cosh[0]), "{:?}", cosh);
+
+    // the scan reads the whole table, so only the random words could tell samples apart
+    let sampler = CdtSampler::<P>::new();
+    let cdt = (0..1024).map(|_| count(&mut rng, &|rng| { sampler.sample(rng); })).collect::<Vec<_>>();
+    assert!(cdt.iter().all(|&c| c == cdt[0]), "{:?}", cdt);
+}
+
+#[cfg(feature = "ct")]
+#[test]
+#[ignore]
+fn test_cdt_sampler_leakage() {
+    use rand::{ ChaChaRng, SeedableRng };
+
+    // the same random words for every sample, against fresh ones
+    let sampler = CdtSampler::<::param::BlissI>::new();
+    let mut seed = 0;
+    let t = ::dudect::leakage(1 << 16, |class| {
+        seed += 1;
+        let mut rng = ChaChaRng::from_seed(&[if class { seed } else { 0 }]);
+        sampler.sample(&mut rng);
+    });
+    assert!(t.abs() < 10.0, "t = {}", t);
+}
+
+#[test]
+fn test_bernoulli_sampler() {
+    all_sets!(P => check_distribution(&BernoulliSampler::<P>::new()));
+}
+
+#[test]
+fn test_cdt_sampler() {
+    all_sets!(P => check_distribution(&CdtSampler::<P>::new()));
+}
+
+#[test]
+fn test_knuth_yao_sampler() {
+    all_sets!(P => check_distribution(&KnuthYaoSampler::<P>::new()));
+}
@@ -1,30 +1,67 @@
-#![feature(iterator_step_by)]
-
 extern crate rand;
 extern crate tiny_keccak;
 extern crate byteorder;
 extern crate bitpack;
 
+#[cfg(test)]
+macro_rules! all_sets {
+    ( $p:ident => $e:expr ) => {{
+        { type $p = ::param::BlissI; $e; }
+        { type $p = ::param::BlissII; $e; }
+        { type $p = ::param::BlissIII; $e; }
+        { type $p = ::param::BlissIV; $e; }
+        { type $p = ::param::BlissV; $e; }
+        #[cfg(feature = "o")]
+        { type $p = ::param::Bliss0; $e; }
+    }}
+}
+
 mod utils;
+mod gauss;
+mod rng;
+mod error;
+#[cfg(all(test, feature = "ct"))] mod dudect;
 mod ntt;
+#[cfg(target_arch = "x86_64")]
+mod avx2;
 mod bliss;
+mod any;
 pub mod param;
+mod variant;
 
-pub use bliss::{ PrivateKey, PublicKey, Signature };
+pub use bliss::{
+    PrivateKey, PublicKey, Signature, PreparedPrivateKey, PreparedPublicKey, Signer, OnlineSigner,
+    BatchItem, verify_batch, verify_batch_threads, sign_batch, sign_batch_threads
+};
+pub use any::{ AnyPublicKey, AnySignature, HEADER_VERSION };
+pub use param::{ ParameterSet, OriginalSet, BlissI, BlissII, BlissIII, BlissIV, BlissV };
+#[cfg(feature = "o")]
+pub use param::Bliss0;
+pub use variant::{ Variant, BlissB, Original };
+pub use error::Error;
+pub use rng::CryptoRng;
+pub use gauss::{ GaussianSampler, DefaultSampler, BernoulliSampler, CdtSampler, KnuthYaoSampler };
 
 
-#[test]
-fn test_sign() {
-    use rand::ChaChaRng;
+#[cfg(test)]
+fn hello_hash() -> [u8; 64] {
     use tiny_keccak::Keccak;
 
     let mut hash = [0; 64];
     let mut sha3 = Keccak::new_sha3_512();
     sha3.update(b"Hello blissb.");
     sha3.finalize(&mut hash);
+    hash
+}
+
+#[cfg(test)]
+fn check_sign<P: ParameterSet>() {
+    use rand::ChaChaRng;
+
+    let hash = hello_hash();
 
     for _ in 0..1024 {
-        let sk = PrivateKey::new::<ChaChaRng>().unwrap();
+        let sk = PrivateKey::<P>::new::<ChaChaRng>().unwrap();
         let pk = sk.public();
         let sign = sk.signature::<ChaChaRng>(&hash).unwrap();
         assert!(pk.verify(&sign, &hash));
@@ -33,31 +70,670 @@ fn test_sign() {
 }
 
 #[test]
-fn test_export_import() {
+fn test_sign() {
+    all_sets!(P => check_sign::<P>());
+}
+
+#[cfg(test)]
+fn check_export_import<P: ParameterSet>() {
     use rand::ChaChaRng;
-    use tiny_keccak::Keccak;
 
-    let mut hash = [0; 64];
-    let mut sha3 = Keccak::new_sha3_512();
-    sha3.update(b"Hello blissb.");
-    sha3.finalize(&mut hash);
+    let hash = hello_hash();
 
     for _ in 0..1024 {
-        let sk = PrivateKey::new::<ChaChaRng>().unwrap();
+        let sk = PrivateKey::<P>::new::<ChaChaRng>().unwrap();
         let pk = sk.public();
         let sign = sk.signature::<ChaChaRng>(&hash).unwrap();
         let sk_bytes = sk.export().unwrap();
         let pk_bytes = pk.export().unwrap();
 
-        let sk2 = PrivateKey::import(&sk_bytes).unwrap();
-        let pk2 = PublicKey::import(&pk_bytes).unwrap();
+        let sk2 = PrivateKey::<P>::import(&sk_bytes).unwrap();
+        let pk2 = PublicKey::<P>::import(&pk_bytes).unwrap();
 
         assert!(pk2.verify(&sign, &hash));
 
         let sign = sk2.signature::<ChaChaRng>(&hash).unwrap();
         let sign_bytes = sign.export().unwrap();
-        let sign2 = Signature::import(&sign_bytes).unwrap();
+        let sign2 = Signature::<P>::import(sign_bytes.as_ref()).unwrap();
+
+        assert!(pk.verify(&sign2, &hash));
+    }
+}
+
+#[test]
+fn test_export_import() {
+    all_sets!(P => check_export_import::<P>());
+}
+
+#[cfg(test)]
+fn check_sign_with<P: ParameterSet, S: GaussianSampler<P>>(sampler: &S) {
+    use rand::ChaChaRng;
+
+    let hash = hello_hash();
+
+    for _ in 0..64 {
+        let sk = PrivateKey::<P>::new::<ChaChaRng>().unwrap();
+        let pk = sk.public();
+        let sign = sk.signature_with::<ChaChaRng, _>(sampler, &hash).unwrap();
+        assert!(pk.verify(&sign, &hash));
+        assert!(!pk.verify(&sign, &[0; 64]));
+    }
+}
+
+#[test]
+fn test_sign_with_sampler() {
+    all_sets!(P => {
+        check_sign_with(&BernoulliSampler::<P>::new());
+        check_sign_with(&CdtSampler::<P>::new());
+        check_sign_with(&KnuthYaoSampler::<P>::new());
+    });
+}
+
+#[test]
+fn test_caller_rng() {
+    use rand::{ ChaChaRng, SeedableRng };
+
+    let hash = hello_hash();
+
+    let mut rng = ChaChaRng::from_seed(&[42]);
+    let sk = PrivateKey::<BlissI>::generate(&mut rng).unwrap();
+    let pk = sk.public();
+    let sign = sk.sign(&mut rng, &hash).unwrap();
+    assert!(pk.verify(&sign, &hash));
+
+    // the same seed reproduces the key and the signature
+    let mut rng = ChaChaRng::from_seed(&[42]);
+    let sk2 = PrivateKey::<BlissI>::generate(&mut rng).unwrap();
+    let sign2 = sk2.sign(&mut rng, &hash).unwrap();
+    assert_eq!(&sk.export().unwrap()[..], &sk2.export().unwrap()[..]);
+    assert_eq!(&sign.export().unwrap()[..], &sign2.export().unwrap()[..]);
+
+    let sign3 = sk.sign_with(&CdtSampler::new(), &mut rng, &hash).unwrap();
+    assert!(pk.verify(&sign3, &hash));
+}
+
+#[cfg(test)]
+fn check_from_seed<P: ParameterSet>(kat: &str) {
+    use tiny_keccak::Keccak;
+
+    let mut seed = [0; 32];
+    for (i, b) in seed.iter_mut().enumerate() {
+        *b = i as u8;
+    }
+
+    let sk = PrivateKey::<P>::from_seed(&seed).unwrap();
+    let sk2 = PrivateKey::<P>::from_seed(&seed).unwrap();
+    seed[31] ^= 1;
+    let sk3 = PrivateKey::<P>::from_seed(&seed).unwrap();
+
+    let sk_bytes = sk.export().unwrap();
+    assert_eq!(sk_bytes.as_ref(), sk2.export().unwrap().as_ref());
+    assert!(sk_bytes.as_ref() != sk3.export().unwrap().as_ref());
+
+    // SHA3-256 of the exported key
+    let mut hash = [0; 32];
+    let mut sha3 = Keccak::new_sha3_256();
+    sha3.update(sk_bytes.as_ref());
+    sha3.finalize(&mut hash);
+    let hex = hash.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    assert_eq!(hex, kat);
+}
+
+#[test]
+fn test_from_seed() {
+    check_from_seed::<BlissI>("dd63bce59b6dfe78e4ed05de919b2c95a66a633ca8a5fa9b91c62a1ef552a450");
+    check_from_seed::<BlissII>("defb353f6d5cfd3624ca0f78d5d642db964a9b02179291289a7cd425212ce89f");
+    check_from_seed::<BlissIII>("1d507bf3256972945b3686e4b7dd74691191a9ed22754b785b51e4c76a0fa656");
+    check_from_seed::<BlissIV>("8159d4c6362c724154a9b698f55b1fbf87f5ee8cfb9c5fdcb0e75803d976c6a0");
+    check_from_seed::<BlissV>("88d2cb9951245d35fb480b50137e43b7181167d5bdf581b1ac9ebb8fbc3b093c");
+    #[cfg(feature = "o")]
+    check_from_seed::<Bliss0>("3b1d231a0e27b37313ac64be8f494a1013235900761bdc0adefbf54b6613099e");
+}
+
+#[test]
+fn test_several_sets() {
+    // keys of every set side by side, with distinct encodings
+    let hash = hello_hash();
+
+    let sk1 = PrivateKey::<BlissI>::from_seed(&[5; 32]).unwrap();
+    let sk4 = PrivateKey::<BlissIV>::from_seed(&[5; 32]).unwrap();
+    let sign1 = sk1.sign_deterministic(&hash).unwrap();
+    let sign4 = sk4.sign_deterministic(&hash).unwrap();
+    assert!(sk1.public().verify(&sign1, &hash));
+    assert!(sk4.public().verify(&sign4, &hash));
+
+    let bytes = sign1.export().unwrap();
+    assert!(Signature::<BlissIV>::import(&bytes).is_err());
+    assert_eq!(BlissI::SIGNATURE_LENGTH, bytes.len());
+    assert_eq!(BlissIV::SIGNATURE_LENGTH, sign4.export().unwrap().len());
+
+    all_sets!(P => {
+        use param::Array;
+        assert_eq!(<P as ParameterSet>::PrivateKeyBytes::zero().as_ref().len(), P::PRIVATEKEY_LENGTH);
+        assert_eq!(<P as ParameterSet>::PrivateKeyFgBytes::zero().as_ref().len(), P::PRIVATEKEY_FG_LENGTH);
+        assert_eq!(<P as ParameterSet>::PublicKeyBytes::zero().as_ref().len(), P::PUBLICKEY_LENGTH);
+        assert_eq!(<P as ParameterSet>::SignatureBytes::zero().as_ref().len(), P::SIGNATURE_LENGTH);
+        assert_eq!(P::W.len(), P::N);
+        assert_eq!(P::R.len(), P::N);
+    });
+}
+
+#[cfg(test)]
+fn check_sign_deterministic<P: ParameterSet>(kat: &str, kat_ct: &str) {
+    use rand::{ ChaChaRng, SeedableRng };
+    use tiny_keccak::Keccak;
+
+    let hash = hello_hash();
+
+    let sk = PrivateKey::<P>::from_seed(&[7; 32]).unwrap();
+    let pk = sk.public();
+
+    let sign = sk.sign_deterministic(&hash).unwrap().export().unwrap();
+    for _ in 0..16 {
+        let sign2 = sk.sign_deterministic(&hash).unwrap();
+        assert!(pk.verify(&sign2, &hash));
+        assert_eq!(sign.as_ref(), sign2.export().unwrap().as_ref());
+    }
+
+    // SHA3-256 of the signature, the same on every platform and across releases.
+    // The samplers of `ct` read the PRF stream differently, so the signature depends on the feature.
+    let mut digest = [0; 32];
+    let mut sha3 = Keccak::new_sha3_256();
+    sha3.update(sign.as_ref());
+    sha3.finalize(&mut digest);
+    let hex = digest.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    assert_eq!(hex, if cfg!(feature = "ct") { kat_ct } else { kat });
+
+    // another message or other extra randomness give another signature
+    let sign2 = sk.sign_deterministic(&[0; 64]).unwrap();
+    assert!(pk.verify(&sign2, &[0; 64]));
+    assert!(sign.as_ref() != sign2.export().unwrap().as_ref());
+
+    let sign2 = sk.sign_prf(&DefaultSampler::default(), b"extra", &hash).unwrap().export().unwrap();
+    let sign3 = sk.sign_prf(&DefaultSampler::default(), b"extra", &hash).unwrap().export().unwrap();
+    assert_eq!(sign2.as_ref(), sign3.as_ref());
+    assert!(sign.as_ref() != sign2.as_ref());
+
+    let mut rng = ChaChaRng::from_seed(&[7]);
+    let sign2 = sk.sign_hedged(&mut rng, &hash).unwrap();
+    assert!(pk.verify(&sign2, &hash));
+    assert!(sign.as_ref() != sign2.export().unwrap().as_ref());
+}
+
+#[test]
+fn test_sign_deterministic() {
+    check_sign_deterministic::<BlissI>(
+        "06d8adc249ddefc696148b8c4afb6e85c9f320d074659eb9210b18158b509668",
+        "8f40c554863d5458932e66a54b16274e5d6fd432813741eff265180c219c366e");
+    check_sign_deterministic::<BlissII>(
+        "8a26d1a43d63dc8b9f25ac434a3195bb599f423d2650acdff7d0269243e8e3fe",
+        "5830d6e9afc4bd2348b5002542906799aa3b295b3051d2a3b8a8b9c3dcf3e68d");
+    check_sign_deterministic::<BlissIII>(
+        "e4a04787b6aba519a1368c2e41e9521bfa0b807c7ad7ed21fff5f8a9600b32ca",
+        "78a3bb72bf43a5870f5af6e17b9c6ba1b37d53bbdb7a01f8380b59798a8b3ebc");
+    check_sign_deterministic::<BlissIV>(
+        "474dbc851a0c528ee07a6f50edb2d00baf8dff922d18c4eda9bd4cee0f928e92",
+        "86d052efeee54930b46be0a34db64f84bdcb3d1d4574895fd907a2ed5b08e7c8");
+    check_sign_deterministic::<BlissV>(
+        "5d142b4c1481a2c87f5faca6ebdf4085c9d4d8f40eef3e1dfccdb9eaa4a22dca",
+        "0c6e62c128cb52962e0718b3d31ca24929ac4e554965282509bd99021ff26e31");
+    #[cfg(feature = "o")]
+    check_sign_deterministic::<Bliss0>(
+        "94d7e4b88f4b0adbcc6cbf27dee24e628afac4239c04385f77def7524c6c519e",
+        "16262b646bb17e600e5c47eb82c24fddde4af29d224bccf83bd51b051f874b35");
+}
+
+#[test]
+fn test_encoding_error() {
+    let sk = PrivateKey::<BlissI>::from_seed(&[0; 32]).unwrap();
+    let mut sign = sk.sign_deterministic(&[0; 64]).unwrap();
+    let sign_bytes = sign.export().unwrap();
+
+    match Signature::<BlissI>::import(&sign_bytes[..sign_bytes.len() - 1]) {
+        Err(Error::Length { .. }) => (),
+        Err(err) => panic!("{}", err),
+        Ok(_) => panic!()
+    }
+
+    sign.t[7] = 1 << BlissI::T_BITS;
+    match sign.export() {
+        Err(Error::Encoding { field: "t", index: 7 }) => (),
+        Err(err) => panic!("{}", err),
+        Ok(_) => panic!()
+    }
+}
+
+#[cfg(test)]
+fn check_strict_import<P: ParameterSet>() {
+    use bitpack::BitPack;
+    use param::Array;
+
+    let sk = PrivateKey::<P>::from_seed(&[0; 32]).unwrap();
+    let pk_bytes = sk.public().export().unwrap();
+    let sign = sk.sign_deterministic(&[0; 64]).unwrap();
+    let sign_bytes = sign.export().unwrap();
+    let sign_bytes = sign_bytes.as_ref();
+    assert_eq!(Signature::<P>::import(sign_bytes).unwrap().export().unwrap().as_ref(), sign_bytes);
+
+    // a coefficient of a public key must be below Q
+    let mut bytes = pk_bytes;
+    bytes[0] = 0xff;
+    bytes[1] |= (1 << (P::A_BITS - 8)) - 1;
+    assert!(PublicKey::<P>::import(&bytes).is_err());
+
+    let reencode = |f: &dyn Fn(&mut Signature<P>)| {
+        let mut sign = Signature::<P>::import(sign_bytes).unwrap();
+        f(&mut sign);
+        let mut bytes = P::SignatureBytes::zero();
+        {
+            let mut bitpack = BitPack::<&mut [u8]>::new(bytes.as_mut());
+            for i in 0..P::N {
+                bitpack.write((sign.t[i] + (1 << (P::T_BITS - 1))) as u32, P::T_BITS).unwrap();
+                bitpack.write((sign.z[i] + (1 << (P::Z_BITS - 1))) as u32, P::Z_BITS).unwrap();
+            }
+            for i in 0..P::KAPPA {
+                bitpack.write(sign.c_idx[i] as u32, P::CIDX_BITS).unwrap();
+            }
+        }
+        bytes
+    };
+
+    assert_eq!(reencode(&|_| ()).as_ref(), sign_bytes);
+    assert!(Signature::<P>::import(reencode(&|sign| sign.z[3] = (P::B_INF >> P::D) + 1).as_ref()).is_err());
+    assert!(Signature::<P>::import(reencode(&|sign| sign.c_idx.as_mut().swap(0, 1)).as_ref()).is_err());
+    assert!(Signature::<P>::import(reencode(&|sign| sign.c_idx[1] = sign.c_idx[0]).as_ref()).is_err());
+
+    // the padding of the last byte must be zero
+    let mut bytes = sign_bytes.to_vec();
+    if (P::CIDX_BITS * P::KAPPA) % 8 != 0 {
+        bytes[P::SIGNATURE_LENGTH - 1] |= 0x80;
+        assert!(Signature::<P>::import(&bytes).is_err());
+    }
+
+    let mut bytes = sign_bytes.to_vec();
+    bytes.push(0);
+    assert!(Signature::<P>::import(&bytes).is_err());
+}
+
+#[test]
+fn test_strict_import() {
+    all_sets!(P => check_strict_import::<P>());
+}
+
+#[cfg(test)]
+fn check_forged_norms<P: ParameterSet>() {
+    use std::cmp;
+
+    let sk = PrivateKey::<P>::from_seed(&[0; 32]).unwrap();
+    let pk = sk.public();
+    let sign_bytes = sk.sign_deterministic(&[0; 64]).unwrap().export().unwrap();
+    let t_max = cmp::min(P::B_INF, (1 << (P::T_BITS - 1)) - 1);
+    let z_max = cmp::min(P::B_INF >> P::D, (1 << (P::Z_BITS - 1)) - 1);
+
+    // every coefficient passes import, but the L2 norm does not fit in an i32
+    for &(t, z) in &[(t_max, 1), (0, z_max), (t_max, z_max)] {
+        let mut forged = Signature::<P>::import(sign_bytes.as_ref()).unwrap();
+        for i in 0..P::N {
+            forged.t[i] = if i % 2 == 0 { t } else { -t };
+            forged.z[i] = if i % 2 == 0 { z } else { -z };
+        }
+        let forged = Signature::<P>::import(forged.export().unwrap().as_ref()).unwrap();
+        assert!(!pk.verify(&forged, &[0; 64]));
+    }
+
+    // the fields are public, so verify must also survive values import never produces
+    let mut forged = Signature::<P>::import(sign_bytes.as_ref()).unwrap();
+    forged.t[0] = i32::MIN;
+    forged.z[1] = i32::MIN;
+    assert!(!pk.verify(&forged, &[0; 64]));
+
+    // c_idx out of range or out of order
+    let mut forged = Signature::<P>::import(sign_bytes.as_ref()).unwrap();
+    forged.c_idx[0] = 100000;
+    assert!(!pk.verify(&forged, &[0; 64]));
+    assert_eq!(verify_batch(&[(sk.public(), forged, [0; 64])]), vec![0]);
+    let mut forged = Signature::<P>::import(sign_bytes.as_ref()).unwrap();
+    forged.c_idx.as_mut().swap(0, 1);
+    assert!(!pk.verify(&forged, &[0; 64]));
+    let mut forged = Signature::<P>::import(sign_bytes.as_ref()).unwrap();
+    forged.c_idx[1] = forged.c_idx[0];
+    assert!(!pk.verify(&forged, &[0; 64]));
+}
+
+#[test]
+fn test_forged_norms() {
+    all_sets!(P => check_forged_norms::<P>());
+}
+
+#[cfg(test)]
+fn check_validate<P: ParameterSet>() {
+    use bitpack::BitPack;
+    use param::Array;
+
+    let sk = PrivateKey::<P>::from_seed(&[1; 32]).unwrap();
+    assert!(sk.validate().is_ok());
+    let sk_bytes = sk.export().unwrap();
+    assert!(PrivateKey::<P>::import_checked(&sk_bytes).is_ok());
+
+    // change f, g or a of sk, and import it without the checks of validate
+    type Edit<T> = dyn Fn(&mut T, &mut T, &mut T);
+    let reencode = |edit: &Edit<P::Poly>| {
+        let (mut f, mut g, mut a) = (*sk.f(), *sk.g(), *sk.a());
+        edit(&mut f, &mut g, &mut a);
+        let mut bytes = P::PrivateKeyBytes::zero();
+        {
+            let mut bitpack = BitPack::<&mut [u8]>::new(bytes.as_mut());
+            for i in 0..P::N {
+                bitpack.write((f[i] + (1 << (P::F_BITS - 1))) as u32, P::F_BITS).unwrap();
+                bitpack.write((g[i] + (1 << (P::G_BITS - 1))) as u32, P::G_BITS).unwrap();
+                bitpack.write(a[i] as u32, P::A_BITS).unwrap();
+            }
+        }
+        PrivateKey::<P>::import(&bytes).unwrap()
+    };
+    assert_eq!(reencode(&|_, _, _| ()).export().unwrap().as_ref(), sk_bytes.as_ref());
+
+    let sk2 = reencode(&|_, _, a| a[5] = (a[5] + 1) % P::Q);
+    assert!(sk2.validate().is_err());
+    let sk2_bytes = sk2.export().unwrap();
+    assert!(PrivateKey::<P>::import_checked(&sk2_bytes).is_err());
+    let sk3 = PrivateKey::<P>::import_recompute(&sk2_bytes).unwrap();
+    assert_eq!(sk3.export().unwrap().as_ref(), sk_bytes.as_ref());
+
+    // f and g must keep the shape of key generation
+    let i = (0..P::N).find(|&i| sk.f()[i] == 0).unwrap();
+    let sk2 = reencode(&move |f, _, _| f[i] = 1);
+    assert!(sk2.validate().is_err());
+    assert!(PrivateKey::<P>::from_fg(sk2.f(), sk.g()).is_err());
+    assert!(reencode(&|_, g, _| g[1] += 1).validate().is_err());
+}
+
+#[test]
+fn test_validate() {
+    all_sets!(P => check_validate::<P>());
+}
+
+#[cfg(test)]
+fn check_compact_export_import<P: ParameterSet>() {
+    use rand::{ ChaChaRng, SeedableRng };
+    use param::PRIVATEKEY_SEED_LENGTH;
+
+    let sk = PrivateKey::<P>::from_seed(&[3; 32]).unwrap();
+    let sk_bytes = sk.export().unwrap();
+    let fg_bytes = sk.export_fg().unwrap();
+    let seed_bytes = sk.export_seed().unwrap();
+    assert_eq!(fg_bytes.as_ref().len(), P::PRIVATEKEY_FG_LENGTH);
+    assert_eq!(seed_bytes.len(), PRIVATEKEY_SEED_LENGTH);
+
+    for bytes in &[sk_bytes.as_ref(), fg_bytes.as_ref(), &seed_bytes[..]] {
+        let sk2 = PrivateKey::<P>::import_any(bytes).unwrap();
+        assert_eq!(sk2.export().unwrap().as_ref(), sk_bytes.as_ref());
+    }
+    assert_eq!(PrivateKey::<P>::import_any(&seed_bytes).unwrap().seed(), Some(&[3; 32]));
+
+    // keys from an rng have no seed, but still the (f, g) encoding
+    let sk = PrivateKey::<P>::generate(&mut ChaChaRng::from_seed(&[3])).unwrap();
+    assert!(sk.export_seed().is_none());
+    let sk2 = PrivateKey::<P>::import_any(sk.export_fg().unwrap().as_ref()).unwrap();
+    assert_eq!(sk2.export().unwrap().as_ref(), sk.export().unwrap().as_ref());
 
+    assert!(PrivateKey::<P>::import_any(&fg_bytes.as_ref()[..P::PRIVATEKEY_FG_LENGTH - 1]).is_err());
+    assert!(PrivateKey::<P>::import_any(&[0xff; PRIVATEKEY_SEED_LENGTH]).is_err());
+    assert!(PrivateKey::<P>::import_any(&[]).is_err());
+}
+
+#[test]
+fn test_compact_export_import() {
+    all_sets!(P => check_compact_export_import::<P>());
+}
+
+#[cfg(test)]
+fn check_original<P: OriginalSet>() {
+    use rand::{ ChaChaRng, SeedableRng };
+    use utils::n_kappa;
+
+    let hash = hello_hash();
+    let mut rng = ChaChaRng::from_seed(&[P::ID as u32]);
+
+    for _ in 0..64 {
+        let sk = PrivateKey::<P, Original>::generate(&mut rng).unwrap();
+        assert!(n_kappa::<P>(sk.f().as_ref(), sk.g().as_ref()) <= P::PMAX_ORIGINAL);
+        sk.validate().unwrap();
+
+        let pk = sk.public();
+        let sign = sk.sign(&mut rng, &hash).unwrap();
+        assert!(pk.verify(&sign, &hash));
+        assert!(!pk.verify(&sign, &[0; 64]));
+
+        let sign2 = Signature::<P, Original>::import(sign.export().unwrap().as_ref()).unwrap();
         assert!(pk.verify(&sign2, &hash));
     }
+
+    // one seed gives different keys for the two variants
+    let sk = PrivateKey::<P, Original>::from_seed(&[7; 32]).unwrap();
+    let sk2 = PrivateKey::<P>::from_seed(&[7; 32]).unwrap();
+    assert!(sk.export().unwrap().as_ref() != sk2.export().unwrap().as_ref());
+
+    // a BLISS-B key beyond the N_kappa bound is no original key
+    let sk = (0..64)
+        .map(|i| PrivateKey::<P>::from_seed(&[i; 32]).unwrap())
+        .find(|sk| n_kappa::<P>(sk.f().as_ref(), sk.g().as_ref()) > P::PMAX_ORIGINAL)
+        .unwrap();
+    assert!(PrivateKey::<P, Original>::from_fg(sk.f(), sk.g()).is_err());
+}
+
+#[test]
+fn test_original() {
+    check_original::<BlissI>();
+    check_original::<BlissII>();
+    check_original::<BlissIII>();
+    check_original::<BlissIV>();
+}
+
+#[cfg(test)]
+fn check_prepared<P: ParameterSet>() {
+    use rand::{ ChaChaRng, SeedableRng };
+
+    let hash = hello_hash();
+    let sk = PrivateKey::<P>::from_seed(&[3; 32]).unwrap();
+    let pk = sk.public();
+    let prepared = PrivateKey::<P>::from_seed(&[3; 32]).unwrap().prepare();
+    let prepared_pk = pk.prepare();
+
+    // the same signatures as the plain key
+    let sign = prepared.sign_deterministic(&hash).unwrap();
+    assert_eq!(sign.export().unwrap().as_ref(), sk.sign_deterministic(&hash).unwrap().export().unwrap().as_ref());
+    let sign2 = prepared.sign(&mut ChaChaRng::from_seed(&[3]), &hash).unwrap();
+    let sign3 = sk.sign(&mut ChaChaRng::from_seed(&[3]), &hash).unwrap();
+    assert_eq!(sign2.export().unwrap().as_ref(), sign3.export().unwrap().as_ref());
+
+    for sign in &[sign, sign2, prepared.sign_hedged(&mut ChaChaRng::from_seed(&[4]), &hash).unwrap()] {
+        assert!(prepared_pk.verify(sign, &hash));
+        assert!(prepared.public().verify(sign, &hash));
+        assert!(pk.verify(sign, &hash));
+        assert!(!prepared_pk.verify(sign, &[0; 64]));
+    }
+}
+
+#[test]
+fn test_prepared() {
+    all_sets!(P => check_prepared::<P>());
+
+    let sk = PrivateKey::<BlissII, Original>::from_seed(&[3; 32]).unwrap().prepare();
+    let sign = sk.sign_deterministic(&hello_hash()).unwrap();
+    assert!(sk.key().public().prepare().verify(&sign, &hello_hash()));
+}
+
+#[cfg(test)]
+fn check_signer<P: ParameterSet>() {
+    use std::thread;
+    use rand::{ ChaChaRng, SeedableRng };
+
+    let hash = hello_hash();
+    let sk = PrivateKey::<P>::from_seed(&[3; 32]).unwrap();
+    let pk = sk.public();
+    let expected = sk.sign_deterministic(&hash).unwrap().export().unwrap();
+
+    // the signer lives on the heap, the thread's stack only holds what signing uses
+    let mut signer = Box::new(Signer::new(sk, ChaChaRng::from_seed(&[3])));
+    let signs = thread::Builder::new()
+        .stack_size(Signer::<P, ChaChaRng>::STACK_BUDGET)
+        .spawn(move || {
+            let mut signs = Vec::new();
+            for _ in 0..4 {
+                signs.push(signer.sign(&hash).unwrap());
+                signs.push(signer.sign_deterministic(&hash).unwrap());
+                signs.push(signer.sign_hedged(&hash).unwrap());
+            }
+            signs
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+
+    assert_eq!(signs[1].export().unwrap().as_ref(), expected.as_ref());
+    assert!(signs[0].export().unwrap().as_ref() != signs[3].export().unwrap().as_ref());
+    for sign in &signs {
+        assert!(pk.verify(sign, &hash));
+    }
+}
+
+#[test]
+fn test_signer() {
+    all_sets!(P => check_signer::<P>());
+}
+
+#[cfg(test)]
+fn check_online<P: ParameterSet>() {
+    use std::{ thread, time };
+    use rand::{ ChaChaRng, SeedableRng };
+
+    let hash = hello_hash();
+    let sk = PrivateKey::<P>::from_seed(&[3; 32]).unwrap();
+    let pk = sk.public();
+
+    // every attempt spends a commitment, accepted or not
+    let mut signer = OnlineSigner::new(PrivateKey::<P>::from_seed(&[3; 32]).unwrap(), ChaChaRng::from_seed(&[3]), 16);
+    assert!(signer.is_empty());
+    assert_eq!(signer.precompute(), 16);
+    assert_eq!(signer.precompute(), 0);
+    let mut left = signer.len();
+    for _ in 0..4 {
+        let sign = signer.sign(&hash).unwrap();
+        assert!(pk.verify(&sign, &hash));
+        assert!(signer.len() < left);
+        left = signer.len();
+    }
+
+    // an empty pool still signs
+    while !signer.is_empty() { signer.sign(&hash).unwrap(); }
+    assert!(pk.verify(&signer.sign(&hash).unwrap(), &hash));
+    assert!(signer.is_empty());
+
+    let mut signer = OnlineSigner::new(sk, ChaChaRng::from_seed(&[4]), 8).background(ChaChaRng::from_seed(&[5]));
+    for _ in 0..1000 {
+        if signer.len() == 8 { break };
+        thread::sleep(time::Duration::from_millis(5));
+    }
+    assert_eq!(signer.len(), 8);
+    for _ in 0..16 {
+        assert!(pk.verify(&signer.sign(&hash).unwrap(), &hash));
+    }
+}
+
+#[test]
+fn test_online() {
+    all_sets!(P => check_online::<P>());
+}
+
+#[cfg(test)]
+fn check_batch<P: ParameterSet>() {
+    let hashes = (0..13u8).map(|i| [i; 64]).collect::<Vec<_>>();
+    let sk = PrivateKey::<P>::from_seed(&[3; 32]).unwrap();
+    let sk2 = PrivateKey::<P>::from_seed(&[4; 32]).unwrap();
+
+    for &threads in &[1, 3, 16] {
+        let signs = sign_batch_threads(&sk, &hashes, threads).unwrap();
+        assert_eq!(signs.len(), hashes.len());
+
+        // a wrong hash at 2, a wrong key at 7 and a swapped signature at 11
+        let mut items = signs.into_iter()
+            .zip(&hashes)
+            .map(|(sign, hash)| (sk.public(), sign, hash.to_vec()))
+            .collect::<Vec<_>>();
+        items[2].2[0] ^= 1;
+        items[7].0 = sk2.public();
+        let sign = sk.sign_deterministic(&hashes[12]).unwrap();
+        items[11].1 = sign;
+        assert_eq!(verify_batch_threads(&items, threads), vec![2, 7, 11]);
+    }
+
+    let items: &[(PublicKey<P>, Signature<P>, [u8; 64])] = &[];
+    assert!(verify_batch(items).is_empty());
+    assert!(sign_batch(&sk, &[] as &[[u8; 64]]).unwrap().is_empty());
+}
+
+#[test]
+fn test_batch() {
+    all_sets!(P => check_batch::<P>());
+}
+
+#[test]
+fn test_debug_redacted() {
+    let sk = PrivateKey::<BlissI>::from_seed(&[9; 32]).unwrap();
+    let out = format!("{:?}", sk);
+    assert!(out.contains("<redacted>"));
+    assert!(!out.contains("[9, 9"));
+    assert!(!out.contains(&format!("{:?}", &sk.f()[..])));
+}
+
+#[test]
+fn test_any_encodings() {
+    let hash = hello_hash();
+
+    let sk1 = PrivateKey::<BlissI>::from_seed(&[6; 32]).unwrap();
+    let sk3 = PrivateKey::<BlissIII>::from_seed(&[6; 32]).unwrap();
+    let pk1 = AnyPublicKey::from(sk1.public()).export().unwrap();
+    let pk3 = AnyPublicKey::from(sk3.public()).export().unwrap();
+    let sign1 = AnySignature::from(sk1.sign_deterministic(&hash).unwrap()).export().unwrap();
+    let sign3 = AnySignature::from(sk3.sign_deterministic(&hash).unwrap()).export().unwrap();
+    assert_eq!(pk1[0], (HEADER_VERSION << 4) | BlissI::ID);
+    assert_eq!(sign3[0], (HEADER_VERSION << 4) | BlissIII::ID);
+    assert_eq!(&pk1[1..], sk1.public().export().unwrap().as_ref());
+
+    // the verifier learns the set from the bytes
+    let pk1 = AnyPublicKey::import(&pk1).unwrap();
+    let pk3 = AnyPublicKey::import(&pk3).unwrap();
+    assert_eq!(pk1.name(), "BLISS-B-I");
+    assert_eq!(pk3.name(), "BLISS-B-III");
+    assert!(pk1.verify(&pk1.import_signature(&sign1).unwrap(), &hash));
+    assert!(pk3.verify(&pk3.import_signature(&sign3).unwrap(), &hash));
+
+    // pairs of different sets are refused
+    match pk1.import_signature(&sign3) {
+        Err(Error::ParameterMismatch) => (),
+        _ => panic!()
+    }
+    assert!(!pk1.verify(&AnySignature::import(&sign3).unwrap(), &hash));
+
+    let mut bytes = sign1.clone();
+    bytes[0] = (HEADER_VERSION + 1) << 4 | BlissI::ID;
+    assert!(AnySignature::import(&bytes).is_err());
+    bytes[0] = (HEADER_VERSION << 4) | 0x0f;
+    assert!(AnySignature::import(&bytes).is_err());
+    match AnySignature::import(&sign1[..sign1.len() - 1]) {
+        Err(Error::Length { expected, found }) => assert_eq!((expected, found), (sign1.len(), sign1.len() - 1)),
+        _ => panic!()
+    }
+    assert!(AnyPublicKey::import(&[]).is_err());
+
+    #[cfg(feature = "o")]
+    {
+        let sk0 = PrivateKey::<Bliss0>::from_seed(&[6; 32]).unwrap();
+        let pk0 = AnyPublicKey::import(&AnyPublicKey::from(sk0.public()).export().unwrap()).unwrap();
+        let sign0 = AnySignature::from(sk0.sign_deterministic(&hash).unwrap()).export().unwrap();
+        assert_eq!(pk0.name(), "BLISS-B-0");
+        assert!(pk0.verify(&pk0.import_signature(&sign0).unwrap(), &hash));
+        assert!(pk1.import_signature(&sign0).is_err());
+    }
 }
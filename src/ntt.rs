@@ -1,4 +1,6 @@
-use ::param::{ Q, N, W };
+use ::param::ParameterSet;
+#[cfg(target_arch = "x86_64")]
+use ::avx2;
 
 
 #[inline] fn muln(x: i32, y: i32, n: i32) -> i32 {
@@ -24,11 +26,190 @@ pub fn pwr(mut x: i32, mut e: i32, n: i32) -> i32 {
     y
 }
 
-pub fn fft(v: &mut [i32]) {
-    let mut j = N >> 1;
-    for i in 1..(N - 1) {
+/// Constants of the reductions, with the Montgomery radix `2^16`.
+pub trait Reduce: ParameterSet {
+    /// `q^-1 mod 2^16`.
+    const QINV: i32 = {
+        let mut x = Self::Q as u32;
+        let mut i = 0;
+        while i < 4 {
+            x = x.wrapping_mul(2u32.wrapping_sub((Self::Q as u32).wrapping_mul(x)));
+            i += 1;
+        }
+        (x & 0xffff) as i32
+    };
+    /// `round(2^26 / q)`.
+    const BARRETT: i32 = ((1 << 26) + Self::Q / 2) / Self::Q;
+    /// `2^16 / n mod q`, the scaling of `inverse`.
+    const SCALE: i32 = {
+        let ninv = (Self::Q - (Self::Q - 1) / Self::N as i32) as i64;
+        let x = (ninv * ((1i64 << 16) % Self::Q as i64) % Self::Q as i64) as i32;
+        if x > Self::Q / 2 { x - Self::Q } else { x }
+    };
+}
+
+impl<P: ParameterSet> Reduce for P {}
+
+/// `x 2^-16 mod q`, in `(-q, q)` for `|x| < q 2^15`.
+#[inline] fn montgomery<P: ParameterSet>(x: i32) -> i32 {
+    let m = x.wrapping_mul(P::QINV) as i16 as i32;
+    (x - m * P::Q) >> 16
+}
+
+/// `x mod q`, in `(-q, q)` for `|x| < 2^31 / round(2^26 / q)`.
+#[inline] fn barrett<P: ParameterSet>(x: i32) -> i32 {
+    let t = (P::BARRETT * x + (1 << 25)) >> 26;
+    x - t * P::Q
+}
+
+/// Step `j` to the next index in bit-reversed order.
+#[inline] fn bitrev_next(mut j: usize, n: usize) -> usize {
+    let mut k = n >> 1;
+    while j & k != 0 {
+        j ^= k;
+        k >>= 1;
+    }
+    j | k
+}
+
+/// Forward negacyclic transform, from `|v[i]| < q` to bit-reversed order and `|v[i]| < q`.
+/// The butterflies are not reduced, after `log n <= 10` layers `|v[i]| < 16 q`.
+fn forward_scalar<P: ParameterSet>(v: &mut [i32]) {
+    let (n, zetas) = (P::N, P::ZETAS);
+
+    let mut len = n >> 1;
+    while len > 0 {
+        let zetas = &zetas[n / (2 * len)..];
+        for (&zeta, chunk) in zetas.iter().zip(v.chunks_mut(2 * len)) {
+            let (lo, hi) = chunk.split_at_mut(len);
+            for (x, y) in lo.iter_mut().zip(hi) {
+                let t = montgomery::<P>(zeta * *y);
+                *y = *x - t;
+                *x += t;
+            }
+        }
+        len >>= 1;
+    }
+
+    for x in v.iter_mut() {
+        *x = barrett::<P>(*x);
+    }
+}
+
+/// Inverse of `forward`, from bit-reversed order and `|v[i]| < q`
+/// to the coefficients in `[0, q)`. The sums are reduced every other layer.
+fn inverse_scalar<P: ParameterSet>(v: &mut [i32]) {
+    let (q, n, zetas) = (P::Q, P::N, P::ZETAS_INV);
+
+    let mut len = 1;
+    let mut reduce = false;
+    while len < n {
+        let zetas = &zetas[n / (2 * len)..];
+        for (&zeta, chunk) in zetas.iter().zip(v.chunks_mut(2 * len)) {
+            let (lo, hi) = chunk.split_at_mut(len);
+            for (x, y) in lo.iter_mut().zip(hi) {
+                let t = *x;
+                *x = t + *y;
+                *y = montgomery::<P>(zeta * (t - *y));
+            }
+            if reduce {
+                for x in lo.iter_mut() {
+                    *x = barrett::<P>(*x);
+                }
+            }
+        }
+        reduce = !reduce;
+        len <<= 1;
+    }
+
+    for x in v.iter_mut() {
+        let y = montgomery::<P>(P::SCALE * *x);
+        *x = if y < 0 { y + q } else { y };
+    }
+}
+
+/// `v[i] a[i]` in `(-q, q)`, the product of `forward` and `a` from `prepare`.
+fn pointwise_scalar<P: ParameterSet>(v: &mut [i32], a: &[i32]) {
+    for (x, &y) in v.iter_mut().zip(a) {
+        *x = montgomery::<P>(*x * y);
+    }
+}
+
+/// The AVX2 transforms are used when the CPU has them.
+#[cfg(target_arch = "x86_64")]
+#[inline] fn has_avx2<P: ParameterSet>() -> bool {
+    P::N >= 16 && is_x86_feature_detected!("avx2")
+}
+
+fn forward<P: ParameterSet>(v: &mut [i32]) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2::<P>() { return unsafe { avx2::forward::<P>(v) } };
+    }
+    forward_scalar::<P>(v)
+}
+
+fn inverse<P: ParameterSet>(v: &mut [i32]) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2::<P>() { return unsafe { avx2::inverse::<P>(v) } };
+    }
+    inverse_scalar::<P>(v)
+}
+
+fn pointwise<P: ParameterSet>(v: &mut [i32], a: &[i32]) {
+    #[cfg(target_arch = "x86_64")]
+    {
+        if has_avx2::<P>() { return unsafe { avx2::pointwise::<P>(v, a) } };
+    }
+    pointwise_scalar::<P>(v, a)
+}
+
+/// The transform of `v` in natural order, in `[0, q)`.
+pub fn ntt<P: ParameterSet>(v: &mut [i32]) {
+    let (q, n) = (P::Q, P::N);
+
+    forward::<P>(v);
+    let mut j = 0;
+    for i in 0..n {
+        if i < j { v.swap(i, j) };
+        j = bitrev_next(j, n);
+    }
+    for x in v.iter_mut() {
+        if *x < 0 { *x += q };
+    }
+}
+
+/// Lay out `a` from `ntt` for `mul`, in bit-reversed order and Montgomery form.
+pub fn prepare<P: ParameterSet>(v: &mut [i32], a: &[i32]) {
+    let (q, n) = (P::Q, P::N);
+
+    let mut j = 0;
+    for x in v.iter_mut() {
+        let y = ((a[j] as i64) << 16).rem_euclid(q as i64) as i32;
+        *x = if y > q / 2 { y - q } else { y };
+        j = bitrev_next(j, n);
+    }
+}
+
+/// `v = t a mod (x^n + 1)` in `[0, q)`, for the coefficients `|t[i]| < q`
+/// and `a` from `prepare`.
+pub fn mul<P: ParameterSet>(v: &mut [i32], t: &[i32], a: &[i32]) {
+    v.copy_from_slice(t);
+    forward::<P>(v);
+    pointwise::<P>(v, a);
+    inverse::<P>(v);
+}
+
+/// The former transform, the reference of the differential tests.
+#[cfg(test)]
+fn fft<P: ParameterSet>(v: &mut [i32]) {
+    let (q, n, w) = (P::Q, P::N, P::W);
+
+    let mut j = n >> 1;
+    for i in 1..(n - 1) {
         if i < j { v.swap(i, j) };
-        let mut k = N;
+        let mut k = n;
         loop {
             k >>= 1;
             j ^= k;
@@ -37,19 +218,19 @@ pub fn fft(v: &mut [i32]) {
     }
 
     let mut i = 1;
-    while i < N {
-        let l = N / i;
+    while i < n {
+        let l = n / i;
 
-        for k in (0..N).step_by(i + i) {
+        for k in (0..n).step_by(i + i) {
             let x = v[k + i];
             v[k + i] = v[k] - x;
             v[k] +=  x;
         }
 
         for j in 1..i {
-            let y = W[j * l];
-            for k in (j..N).step_by(i + i) {
-                let x = muln(v[k + i], y, Q);
+            let y = w[j * l];
+            for k in (j..n).step_by(i + i) {
+                let x = muln(v[k + i], y, q);
                 v[k + i] = v[k] - x;
                 v[k] += x;
             }
@@ -59,21 +240,20 @@ pub fn fft(v: &mut [i32]) {
     }
 }
 
-pub fn xmu(v: &mut [i32], t: &[i32], u: &[i32]) {
-    for i in 0..N {
-        v[i] = muln(t[i], u[i], Q);
-    }
-}
+#[cfg(test)]
+fn xmu<P: ParameterSet>(v: &mut [i32], t: &[i32], u: &[i32]) {
+    let (q, n) = (P::Q, P::N);
 
-pub fn cmu(v: &mut [i32], t: &[i32], c: i32) {
-    for i in 0..N {
-        let x = muln(t[i], c, Q);
-        v[i] = if x < 0 { x + Q } else { x };
+    for i in 0..n {
+        v[i] = muln(t[i], u[i], q);
     }
 }
 
-pub fn flp(v: &mut [i32]) {
-    let (mut i, mut j) = (1, N - 1);
+#[cfg(test)]
+fn flp<P: ParameterSet>(v: &mut [i32]) {
+    let (q, n) = (P::Q, P::N);
+
+    let (mut i, mut j) = (1, n - 1);
     while i < j {
         v.swap(i, j);
         i += 1;
@@ -81,10 +261,151 @@ pub fn flp(v: &mut [i32]) {
     }
     v[0] = -v[0];
 
-    for i in 0..N {
-        let mut x = v[i];
-        if x < 0 { x += Q };
-        if x >= Q { x -= Q };
-        v[i] = x;
+    for x in &mut v[..n] {
+        if *x < 0 { *x += q };
+        if *x >= q { *x -= q };
+    }
+}
+
+#[test]
+fn test_negacyclic_product() {
+    use rand::{ Rng, ChaChaRng, SeedableRng };
+    use ::param::Array;
+
+    fn check<P: ParameterSet>() {
+        let (q, n) = (P::Q, P::N);
+        let mut rng = ChaChaRng::from_seed(&[P::ID as u32]);
+        let (mut t, mut a) = (P::Poly::zero(), P::Poly::zero());
+        for i in 0..n {
+            t[i] = rng.gen_range(0, q);
+            a[i] = rng.gen_range(0, q);
+        }
+
+        // t * a mod (x^n + 1), schoolbook
+        let mut expected = vec![0i64; n];
+        for i in 0..n {
+            for j in 0..n {
+                let x = t[i] as i64 * a[j] as i64 % q as i64;
+                if i + j < n {
+                    expected[i + j] += x;
+                } else {
+                    expected[i + j - n] -= x;
+                }
+            }
+        }
+
+        let (mut u, mut v) = (P::Poly::zero(), P::Poly::zero());
+        ntt::<P>(a.as_mut());
+        prepare::<P>(u.as_mut(), a.as_ref());
+        mul::<P>(v.as_mut(), t.as_ref(), u.as_ref());
+
+        for i in 0..n {
+            assert_eq!(v[i] as i64, expected[i].rem_euclid(q as i64), "{} [{}]", P::NAME, i);
+        }
+    }
+
+    all_sets!(P => check::<P>());
+}
+
+#[test]
+fn test_differential() {
+    use rand::{ Rng, ChaChaRng, SeedableRng };
+    use ::param::Array;
+
+    fn check<P: ParameterSet>() {
+        let (q, n) = (P::Q, P::N);
+        let mut rng = ChaChaRng::from_seed(&[P::ID as u32]);
+        let (mut t, mut a) = (P::Poly::zero(), P::Poly::zero());
+        let (mut u, mut v, mut w) = (P::Poly::zero(), P::Poly::zero(), P::Poly::zero());
+
+        for round in 0..64 {
+            // the extremes of the input bounds, then random
+            for i in 0..n {
+                t[i] = match round {
+                    0 => q - 1,
+                    1 => 1 - q,
+                    2 => if i & 1 == 0 { q - 1 } else { 1 - q },
+                    _ => rng.gen_range(1 - q, q)
+                };
+                a[i] = if round < 3 { q - 1 } else { rng.gen_range(0, q) };
+            }
+
+            // the transform
+            v.as_mut().copy_from_slice(t.as_ref());
+            ntt::<P>(v.as_mut());
+            xmu::<P>(u.as_mut(), t.as_ref(), P::W);
+            fft::<P>(u.as_mut());
+            for i in 0..n {
+                assert_eq!(v[i], u[i].rem_euclid(q), "{} ntt [{}]", P::NAME, i);
+            }
+
+            // the product
+            prepare::<P>(w.as_mut(), a.as_ref());
+            mul::<P>(v.as_mut(), t.as_ref(), w.as_ref());
+            xmu::<P>(u.as_mut(), t.as_ref(), P::W);
+            fft::<P>(u.as_mut());
+            xmu::<P>(w.as_mut(), u.as_ref(), a.as_ref());
+            fft::<P>(w.as_mut());
+            xmu::<P>(u.as_mut(), w.as_ref(), P::R);
+            flp::<P>(u.as_mut());
+            assert_eq!(v.as_ref(), u.as_ref(), "{} mul", P::NAME);
+        }
+    }
+
+    all_sets!(P => check::<P>());
+}
+
+#[cfg(target_arch = "x86_64")]
+#[test]
+fn test_avx2_identical() {
+    use std::env;
+    use rand::{ Rng, ChaChaRng, SeedableRng };
+    use ::param::Array;
+
+    fn check<P: ParameterSet>() {
+        let (q, n) = (P::Q, P::N);
+        let mut rng = ChaChaRng::from_seed(&[P::ID as u32]);
+        let (mut t, mut a) = (P::Poly::zero(), P::Poly::zero());
+        let (mut u, mut v) = (P::Poly::zero(), P::Poly::zero());
+
+        for round in 0..64 {
+            for i in 0..n {
+                t[i] = match round {
+                    0 => q - 1,
+                    1 => 1 - q,
+                    2 => if i & 1 == 0 { q - 1 } else { 1 - q },
+                    _ => rng.gen_range(1 - q, q)
+                };
+                a[i] = if round < 3 { q - 1 } else { rng.gen_range(0, q) };
+            }
+
+            u.as_mut().copy_from_slice(t.as_ref());
+            v.as_mut().copy_from_slice(t.as_ref());
+            forward_scalar::<P>(u.as_mut());
+            unsafe { avx2::forward::<P>(v.as_mut()) };
+            assert_eq!(u.as_ref(), v.as_ref(), "{} forward", P::NAME);
+
+            pointwise_scalar::<P>(u.as_mut(), a.as_ref());
+            unsafe { avx2::pointwise::<P>(v.as_mut(), a.as_ref()) };
+            assert_eq!(u.as_ref(), v.as_ref(), "{} pointwise", P::NAME);
+
+            inverse_scalar::<P>(u.as_mut());
+            unsafe { avx2::inverse::<P>(v.as_mut()) };
+            assert_eq!(u.as_ref(), v.as_ref(), "{} inverse", P::NAME);
+
+            // the inverse alone, from unreduced inputs
+            u.as_mut().copy_from_slice(t.as_ref());
+            v.as_mut().copy_from_slice(t.as_ref());
+            inverse_scalar::<P>(u.as_mut());
+            unsafe { avx2::inverse::<P>(v.as_mut()) };
+            assert_eq!(u.as_ref(), v.as_ref(), "{} inverse", P::NAME);
+        }
+    }
+
+    // a missing AVX2 would otherwise pass without comparing anything
+    if !is_x86_feature_detected!("avx2") {
+        assert!(env::var_os("BLISSB_NO_AVX2").is_some(), "no AVX2, set BLISSB_NO_AVX2 to skip");
+        return;
     }
+    all_sets!(P => check::<P>());
 }
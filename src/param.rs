@@ -1,282 +1,642 @@
+use std::fmt;
+use std::ops::{ Index, IndexMut };
+
+
+/// Format tags of the compact private key encodings.
+pub const FORMAT_FG: u8 = 0x01;
+pub const FORMAT_SEED: u8 = 0x02;
+
+pub const PRIVATEKEY_SEED_LENGTH: usize = 1 + 32;
+
+/// Fixed size arrays, the storage of polynomials and encodings.
+pub trait Array<T>: Copy + fmt::Debug + AsRef<[T]> + AsMut<[T]>
+    + Index<usize, Output = T> + IndexMut<usize> + Send + Sync
+{
+    fn zero() -> Self;
+}
+
+impl<T: Copy + Default + fmt::Debug + Send + Sync, const L: usize> Array<T> for [T; L] {
+    fn zero() -> [T; L] {
+        [T::default(); L]
+    }
+}
+
+/// A BLISS-B parameter set.
+pub trait ParameterSet: Copy + Default + fmt::Debug + Send + Sync + 'static {
+    /// Identifier of the set in the header of self-describing encodings.
+    const ID: u8;
+    const NAME: &'static str;
+
+    const Q: i32;
+    const N: usize;
+    const D: i32;
+    const P: i32;
+    const KAPPA: usize;
+    const B_INF: i32;
+    const B_L2: i32;
+    const NZ1: i32;
+    const NZ2: i32;
+    const PMAX: i32;
+    const SIGMA: f64;
+    const M: f64;
+
+    const F_BITS: usize;
+    const G_BITS: usize;
+    const A_BITS: usize;
+    const T_BITS: usize;
+    const Z_BITS: usize;
+    const CIDX_BITS: usize;
+
+    // sigma = K_SIGMA * sqrt(1 / (2 ln 2)), EXP_TABLE[i] = 2^64 * exp(-2^i / (2 sigma^2))
+    const K_SIGMA: i32;
+    const EXP_TABLE: &'static [u64];
+
+    /// Twiddle tables of the negacyclic NTT, see `ntt.rs`.
+    const W: &'static [i32];
+    const R: &'static [i32];
+    /// `psi^br(k)` and `psi^-br(k)` in Montgomery form, in bit-reversed order.
+    const ZETAS: &'static [i32];
+    const ZETAS_INV: &'static [i32];
+
+    const PRIVATEKEY_LENGTH: usize =
+        (Self::F_BITS * Self::N / 8) + (Self::G_BITS * Self::N / 8) + (Self::A_BITS * Self::N / 8);
+    const PRIVATEKEY_FG_LENGTH: usize = 1 + (Self::F_BITS * Self::N / 8) + (Self::G_BITS * Self::N / 8);
+    const PUBLICKEY_LENGTH: usize = Self::A_BITS * Self::N / 8;
+    const SIGNATURE_LENGTH: usize =
+        (Self::T_BITS * Self::N / 8) + (Self::Z_BITS * Self::N / 8)
+        + (Self::CIDX_BITS * Self::KAPPA).div_ceil(8);
+
+    /// `N` coefficients.
+    type Poly: Array<i32>;
+    /// `KAPPA` indices.
+    type Index: Array<usize>;
+    /// Room for the positions of the nonzero coefficients of a polynomial.
+    type Support: Array<u16>;
+
+    type PrivateKeyBytes: Array<u8>;
+    type PrivateKeyFgBytes: Array<u8>;
+    type PublicKeyBytes: Array<u8>;
+    type SignatureBytes: Array<u8>;
+}
+
+/// A set that also has the parameters of the original BLISS, for `variant::Original`.
+pub trait OriginalSet: ParameterSet {
+    /// Bound on `N_kappa(S)` at key generation, and so on `|Sc|^2`.
+    const PMAX_ORIGINAL: i32;
+    /// `M = exp(1 / (2 alpha^2))`.
+    const M_ORIGINAL: f64;
+    const B_L2_ORIGINAL: i32;
+}
+
+/// `sqrt(2 ln 2)`, so that `sigma = K_SIGMA / SQRT_2LN2`.
+const SQRT_2LN2: f64 = 1.1774100225154747;
+/// `ln 2` with `FIX` fractional bits.
+const LN2_FIX: u128 = 0xb17217f7d1cf79abc9e3b39803f2f6a;
+const FIX: u32 = 124;
+
+/// The free choices of a parameter set, the rest is computed by `derive`.
+#[derive(Clone, Copy, Debug)]
+pub struct Spec {
+    pub q: i32,
+    pub n: usize,
+    pub d: i32,
+    pub kappa: usize,
+    pub sigma: f64,
+    pub nz1: i32,
+    pub nz2: i32,
+    b_inf: Option<i32>,
+    b_l2: Option<i32>,
+    t_bits: Option<usize>,
+    z_bits: Option<usize>
+}
+
+/// A parameter set computed by `Spec::derive`, see `ParameterSet` for the meaning of the fields.
+#[derive(Clone, Copy, Debug)]
+pub struct Params {
+    pub q: i32,
+    pub n: usize,
+    pub d: i32,
+    pub p: i32,
+    pub kappa: usize,
+    pub b_inf: i32,
+    pub b_l2: i32,
+    pub nz1: i32,
+    pub nz2: i32,
+    pub pmax: i32,
+    pub sigma: f64,
+    pub m: f64,
+    pub f_bits: usize,
+    pub g_bits: usize,
+    pub a_bits: usize,
+    pub t_bits: usize,
+    pub z_bits: usize,
+    pub cidx_bits: usize,
+    pub k_sigma: i32,
+    pub exp_len: usize
+}
+
+impl Spec {
+    pub const fn new(q: i32, n: usize, d: i32, kappa: usize, sigma: f64, nz1: i32, nz2: i32) -> Spec {
+        Spec {
+            q, n, d, kappa, sigma, nz1, nz2,
+            b_inf: None, b_l2: None, t_bits: None, z_bits: None
+        }
+    }
+
+    /// Use a published `B_INF`, which must be within 1% of the derived one.
+    pub const fn b_inf(mut self, b_inf: i32) -> Spec {
+        self.b_inf = Some(b_inf);
+        self
+    }
+
+    /// Use a published `B_L2`, which must be within 2% of the derived one.
+    pub const fn b_l2(mut self, b_l2: i32) -> Spec {
+        self.b_l2 = Some(b_l2);
+        self
+    }
+
+    /// Use a published width of `t`, at most one bit off the derived one.
+    pub const fn t_bits(mut self, t_bits: usize) -> Spec {
+        self.t_bits = Some(t_bits);
+        self
+    }
+
+    /// Use a published width of `z`, at most one bit off the derived one.
+    pub const fn z_bits(mut self, z_bits: usize) -> Spec {
+        self.z_bits = Some(z_bits);
+        self
+    }
+
+    /// Compute the parameter set, panics on inconsistent choices,
+    /// which is a compile error when evaluated in a constant.
+    pub const fn derive(self) -> Params {
+        let Spec { q, n, d, kappa, sigma, nz1, nz2, .. } = self;
+
+        assert!(n.is_power_of_two() && n >= 8, "n must be a power of two");
+        assert!(is_prime(q), "q must be prime");
+        assert!((q - 1) % (2 * n as i32) == 0, "q must be 1 modulo 2n");
+        assert!(q < 1 << 14 && n <= 1024, "q and n too large for the reductions of ntt.rs");
+        assert!(kappa <= n && nz1 >= 0 && nz2 >= 0 && (nz1 + nz2) as usize <= n, "too many nonzero coefficients");
+        assert!(d >= 1 && (2 * q) >> d >= 2, "d too large for q");
+
+        // f has coefficients in [-2, 2], or [-1, 1] without nz2, and g = 2 g' - 1
+        let max_f = if nz2 > 0 { 2 } else { 1 };
+        // the largest |s_i|^2 of a column of S = (f, g), g[0] adds 5 or 9,
+        // and GreedySC keeps |Sc|^2 below kappa times it
+        let pmax = kappa as i32 * (5 * nz1 + 20 * nz2 + if nz2 > 0 { 9 } else { 5 });
+
+        let k_sigma = ceil(sigma * SQRT_2LN2);
+        let exp_len = exp_table_len(k_sigma);
+        assert!((pmax as u64) >> exp_len == 0, "PMAX beyond EXP_TABLE");
+        let sigma2 = k_sigma as f64 / SQRT_2LN2;
+
+        // tail bounds of t and of the compressed z, with margin
+        let b_inf = (1 << d) + ceil(5.0 * sigma);
+        let b_inf = match self.b_inf {
+            Some(b) => {
+                assert!(within(b as f64, b_inf as f64, 0.01), "B_INF far from 2^d + 5 sigma");
+                b
+            },
+            None => b_inf
+        };
+        let b_l2 = 2.0 * n as f64 * (1.1 * sigma) * (1.1 * sigma) + n as f64 * (1u64 << (2 * d)) as f64 / 5.0;
+        assert!(b_l2 < i32::MAX as f64, "B_L2 overflows");
+        let b_l2 = ceil(b_l2);
+        let b_l2 = match self.b_l2 {
+            Some(b) => {
+                assert!(within(b as f64, b_l2 as f64, 0.02), "B_L2 far from 2n (1.1 sigma)^2 + n 4^d / 5");
+                b
+            },
+            None => b_l2
+        };
+
+        let t_bits = bits(b_inf) + 1;
+        let t_bits = match self.t_bits {
+            Some(t) => {
+                assert!(t + 1 >= t_bits && t <= t_bits + 1, "T_BITS far from the width of B_INF");
+                t
+            },
+            None => t_bits
+        };
+        let z_bits = bits(b_inf >> d) + 1;
+        let z_bits = match self.z_bits {
+            Some(z) => {
+                assert!(z + 1 >= z_bits && z <= z_bits + 1, "Z_BITS far from the width of B_INF >> d");
+                z
+            },
+            None => z_bits
+        };
+
+        Params {
+            q,
+            n,
+            d,
+            p: (2 * q) >> d,
+            kappa,
+            b_inf,
+            b_l2,
+            nz1,
+            nz2,
+            pmax,
+            sigma,
+            m: exp(pmax as f64 / (2.0 * sigma2 * sigma2)),
+            f_bits: bits(max_f) + 1,
+            g_bits: bits(2 * max_f + 1) + 1,
+            a_bits: bits(q - 1),
+            t_bits,
+            z_bits,
+            cidx_bits: bits(n as i32 - 1),
+            k_sigma,
+            exp_len
+        }
+    }
+}
+
+/// The original BLISS parameters of a set, computed by `Params::original`.
+#[derive(Clone, Copy, Debug)]
+pub struct OriginalParams {
+    pub alpha: f64,
+    pub pmax: i32,
+    pub m: f64,
+    pub b_l2: i32
+}
+
+impl Params {
+    /// `PMAX = 2 sigma^2 ln M = sigma^2 / alpha^2` of the original BLISS, whose keys have
+    /// no `GreedySC` to lower `|Sc|^2`. `b_l2` must be within 1% of the BLISS-B one.
+    pub const fn original(&self, alpha: f64, b_l2: i32) -> OriginalParams {
+        let sigma = self.k_sigma as f64 / SQRT_2LN2;
+        let pmax = (sigma * sigma / (alpha * alpha)) as i32;
+        assert!(pmax >= self.pmax, "alpha too large");
+        assert!((pmax as u64) >> self.exp_len == 0, "PMAX beyond EXP_TABLE");
+        assert!(within(b_l2 as f64, self.b_l2 as f64, 0.01), "B_L2 far from BLISS-B");
+
+        OriginalParams {
+            alpha,
+            pmax,
+            m: exp(pmax as f64 / (2.0 * sigma * sigma)),
+            b_l2
+        }
+    }
+
+    pub const fn privatekey_length(&self) -> usize {
+        (self.f_bits + self.g_bits + self.a_bits) * self.n / 8
+    }
+
+    pub const fn privatekey_fg_length(&self) -> usize {
+        1 + (self.f_bits + self.g_bits) * self.n / 8
+    }
+
+    pub const fn publickey_length(&self) -> usize {
+        self.a_bits * self.n / 8
+    }
+
+    pub const fn signature_length(&self) -> usize {
+        (self.t_bits + self.z_bits) * self.n / 8 + (self.cidx_bits * self.kappa).div_ceil(8)
+    }
+}
+
+/// Bits of a nonnegative `x`.
+const fn bits(x: i32) -> usize {
+    (32 - (x as u32).leading_zeros()) as usize
+}
+
+const fn ceil(x: f64) -> i32 {
+    let y = x as i32;
+    if (y as f64) < x { y + 1 } else { y }
+}
+
+const fn within(x: f64, y: f64, tolerance: f64) -> bool {
+    x <= y * (1.0 + tolerance) && x >= y * (1.0 - tolerance)
+}
+
+/// `e^x` for small `x`.
+const fn exp(x: f64) -> f64 {
+    let (mut sum, mut term, mut k) = (1.0, 1.0, 1);
+    while k < 64 {
+        term = term * x / k as f64;
+        sum += term;
+        k += 1;
+    }
+    sum
+}
+
+const fn pow_mod(x: i32, mut e: i32, q: i32) -> i32 {
+    let (mut x, mut y) = (x as i64, 1);
+    while e > 0 {
+        if e & 1 != 0 { y = y * x % q as i64 };
+        x = x * x % q as i64;
+        e >>= 1;
+    }
+    y as i32
+}
+
+const fn is_prime(q: i32) -> bool {
+    let mut i = 2;
+    while i * i <= q {
+        if q % i == 0 { return false };
+        i += 1;
+    }
+    q > 1
+}
+
+/// The least primitive root modulo the prime `q`.
+const fn primitive_root(q: i32) -> i32 {
+    let mut g = 2;
+    loop {
+        let (mut m, mut p, mut ok) = (q - 1, 2, true);
+        while m > 1 {
+            if m % p == 0 {
+                if pow_mod(g, (q - 1) / p, q) == 1 { ok = false };
+                while m % p == 0 { m /= p };
+            }
+            p += 1;
+        }
+        if ok { return g };
+        g += 1;
+    }
+}
+
+/// `W[i] = psi^i`, for the 2n-th root of unity `psi = g^((q - 1) / 2n)`
+/// of the least primitive root `g`.
+pub const fn w_table<const L: usize>(q: i32) -> [i32; L] {
+    let psi = pow_mod(primitive_root(q), (q - 1) / (2 * L as i32), q);
+    let mut w = [0; L];
+    let (mut i, mut x) = (0, 1);
+    while i < L {
+        w[i] = x as i32;
+        x = x * psi as i64 % q as i64;
+        i += 1;
+    }
+    w
+}
+
+/// `R[i] = -psi^i / n`, undoing the scaling of the inverse transform.
+pub const fn r_table<const L: usize>(q: i32) -> [i32; L] {
+    let w = w_table::<L>(q);
+    let c = (q - pow_mod(L as i32, q - 2, q)) as i64;
+    let mut r = [0; L];
+    let mut i = 0;
+    while i < L {
+        r[i] = (c * w[i] as i64 % q as i64) as i32;
+        i += 1;
+    }
+    r
+}
+
+/// `psi^br(k) 2^16`, or `psi^-br(k) 2^16` if `inverse`, centered around zero.
+pub const fn zeta_table<const L: usize>(q: i32, inverse: bool) -> [i32; L] {
+    let psi = pow_mod(primitive_root(q), (q - 1) / (2 * L as i32), q);
+    let bits = L.trailing_zeros();
+    let mut zetas = [0; L];
+    let mut k = 0;
+    while k < L {
+        let e = if bits == 0 { 0 } else { k.reverse_bits() >> (usize::BITS - bits) } as i32;
+        let e = if inverse { 2 * L as i32 - e } else { e };
+        let x = (pow_mod(psi, e, q) as i64 * (1 << 16) % q as i64) as i32;
+        zetas[k] = if x > q / 2 { x - q } else { x };
+        k += 1;
+    }
+    zetas
+}
+
+/// `(x * y) >> FIX` for `x, y < 2^(FIX + 1)`, without overflow.
+const fn mul_fix(x: u128, y: u128) -> u128 {
+    let (x1, x0) = (x >> 64, x & 0xffff_ffff_ffff_ffff);
+    let (y1, y0) = (y >> 64, y & 0xffff_ffff_ffff_ffff);
+    let (lo, mid, hi) = (x0 * y0, x1 * y0 + x0 * y1, x1 * y1);
+    let (lo, carry) = lo.overflowing_add(mid << 64);
+    let hi = hi + (mid >> 64) + carry as u128;
+    (hi << (128 - FIX)) | (lo >> FIX)
+}
+
+/// Rows of `EXP_TABLE`, up to the first that rounds to zero.
+pub const fn exp_table_len(k_sigma: i32) -> usize {
+    let kk = (k_sigma as u64) * (k_sigma as u64);
+    let mut i = 0;
+    while (1 << i) <= 64 * kk {
+        i += 1;
+    }
+    i
+}
+
+/// `EXP_TABLE[i] = floor(2^64 exp(-2^i / (2 sigma^2))) = floor(2^(64 - 2^i / K_SIGMA^2))`.
+pub const fn exp_table<const L: usize>(k_sigma: i32) -> [u64; L] {
+    let kk = (k_sigma as u128) * (k_sigma as u128);
+    let mut table = [0; L];
+    let mut i = 0;
+    while i < L {
+        // 64 - 2^i / kk = e + r / kk
+        let x = 64 * kk - (1 << i);
+        let (e, r) = (x / kk, x % kk);
+
+        // 2^(r / kk) = exp(r ln 2 / kk) by its series
+        let y = LN2_FIX / kk * r;
+        let (mut sum, mut term, mut k) = (1 << FIX, 1 << FIX, 1);
+        while k < 48 {
+            term = mul_fix(term, y) / k;
+            sum += term;
+            k += 1;
+        }
+
+        table[i] = (sum >> (FIX as u128 - e)) as u64;
+        i += 1;
+    }
+    table
+}
+
+/// Define a parameter set `$set` from a `Spec`, deriving everything else at compile time.
+/// `original alpha, B_L2` also makes it an `OriginalSet`. Outside this crate the `ID`
+/// should not be one of the sets here, 0 to 5, as it tags the encodings of `AnyPublicKey`.
+///
+/// ```
+/// #[macro_use] extern crate blissb;
+///
+/// use blissb::{ PrivateKey, ParameterSet };
+/// use blissb::param::Spec;
+///
+/// bliss_param!(
+///     /// BLISS-B-I with a smaller challenge, everything else derived.
+///     MyBliss = 9, "MY-BLISS";
+///     Spec::new(12289, 512, 10, 20, 215.0, 154, 0)
+/// );
+///
+/// fn main() {
+///     assert_eq!(MyBliss::KAPPA, 20);
+///     let sk = PrivateKey::<MyBliss>::from_seed(&[0; 32]).unwrap();
+///     let sign = sk.sign_deterministic(&[0; 64]).unwrap();
+///     assert!(sk.public().verify(&sign, &[0; 64]));
+/// }
+/// ```
+#[macro_export]
 macro_rules! bliss_param {
     (
-        $q:expr, $n:expr, $d:expr, $p:expr, $kappa:expr, $b_inf:expr, $b_l2:expr,
-        $nz1:expr, $nz2:expr, $pmax:expr, $sigma:expr, $m:expr,
-        $f:expr, $g:expr, $a:expr, $t:expr, $z:expr, $cidx:expr
+        $(#[$attr:meta])* $set:ident = $id:expr, $name:expr;
+        $spec:expr $(; original $alpha:expr, $b_l2:expr)?
     ) => {
-        pub const Q: i32 = $q;
-        pub const N: usize = $n;
-        pub const D: i32 = $d;
-        pub const P: i32 = $p;
-        pub const KAPPA: usize = $kappa;
-        pub const B_INF: i32 = $b_inf;
-        pub const B_L2: i32 = $b_l2;
-        pub const NZ1: i32 = $nz1;
-        pub const NZ2: i32 = $nz2;
-        pub const PMAX: i32 = $pmax;
-        pub const SIGMA: f64 = $sigma;
-        pub const M: f64 = $m;
-
-        pub const F_BITS: usize = $f;
-        pub const G_BITS: usize = $g;
-        pub const A_BITS: usize = $a;
-        pub const T_BITS: usize = $t;
-        pub const Z_BITS: usize = $z;
-        pub const CIDX_BITS: usize = $cidx;
-
-        pub const PRIVATEKEY_LENGTH: usize =
-            (F_BITS * N / 8) + (G_BITS * N / 8) + (A_BITS * N / 8);
-        pub const PUBLICKEY_LENGTH: usize = A_BITS * N / 8;
-        pub const SIGNATURE_LENGTH: usize =
-            (T_BITS * N / 8) + (Z_BITS * N / 8) + ((CIDX_BITS * KAPPA + 8 - 1) / 8);
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+        pub struct $set;
+
+        impl $set {
+            pub const PARAMS: $crate::param::Params = $spec.derive();
+        }
+
+        impl $crate::param::ParameterSet for $set {
+            const ID: u8 = $id;
+            const NAME: &'static str = $name;
+
+            const Q: i32 = $set::PARAMS.q;
+            const N: usize = $set::PARAMS.n;
+            const D: i32 = $set::PARAMS.d;
+            const P: i32 = $set::PARAMS.p;
+            const KAPPA: usize = $set::PARAMS.kappa;
+            const B_INF: i32 = $set::PARAMS.b_inf;
+            const B_L2: i32 = $set::PARAMS.b_l2;
+            const NZ1: i32 = $set::PARAMS.nz1;
+            const NZ2: i32 = $set::PARAMS.nz2;
+            const PMAX: i32 = $set::PARAMS.pmax;
+            const SIGMA: f64 = $set::PARAMS.sigma;
+            const M: f64 = $set::PARAMS.m;
+
+            const F_BITS: usize = $set::PARAMS.f_bits;
+            const G_BITS: usize = $set::PARAMS.g_bits;
+            const A_BITS: usize = $set::PARAMS.a_bits;
+            const T_BITS: usize = $set::PARAMS.t_bits;
+            const Z_BITS: usize = $set::PARAMS.z_bits;
+            const CIDX_BITS: usize = $set::PARAMS.cidx_bits;
+
+            const K_SIGMA: i32 = $set::PARAMS.k_sigma;
+            const EXP_TABLE: &'static [u64] =
+                &$crate::param::exp_table::<{ $set::PARAMS.exp_len }>($set::PARAMS.k_sigma);
+
+            const W: &'static [i32] = &$crate::param::w_table::<{ $set::PARAMS.n }>($set::PARAMS.q);
+            const R: &'static [i32] = &$crate::param::r_table::<{ $set::PARAMS.n }>($set::PARAMS.q);
+            const ZETAS: &'static [i32] = &$crate::param::zeta_table::<{ $set::PARAMS.n }>($set::PARAMS.q, false);
+            const ZETAS_INV: &'static [i32] = &$crate::param::zeta_table::<{ $set::PARAMS.n }>($set::PARAMS.q, true);
+
+            type Poly = [i32; $set::PARAMS.n];
+            type Index = [usize; $set::PARAMS.kappa];
+            type Support = [u16; $set::PARAMS.n];
+
+            type PrivateKeyBytes = [u8; $set::PARAMS.privatekey_length()];
+            type PrivateKeyFgBytes = [u8; $set::PARAMS.privatekey_fg_length()];
+            type PublicKeyBytes = [u8; $set::PARAMS.publickey_length()];
+            type SignatureBytes = [u8; $set::PARAMS.signature_length()];
+        }
+
+        $(
+            impl $set {
+                pub const ORIGINAL: $crate::param::OriginalParams = $set::PARAMS.original($alpha, $b_l2);
+            }
+
+            impl $crate::param::OriginalSet for $set {
+                const PMAX_ORIGINAL: i32 = $set::ORIGINAL.pmax;
+                const M_ORIGINAL: f64 = $set::ORIGINAL.m;
+                const B_L2_ORIGINAL: i32 = $set::ORIGINAL.b_l2;
+            }
+        )?
     }
 }
 
-#[cfg(feature = "i")]
+// The published sets keep their verification bounds and encoding widths,
+// and those of BLISS-I to IV, with the alpha of their M.
+
 bliss_param!(
-    12289,  512,    10,     24,     23,     2100,   12872 * 12872,
-    154,    0,      17825,  215.0,  1.21,
-    2,      3,      14,     12,     3,      9
+    /// BLISS-B-I, 128-bit security.
+    BlissI = 1, "BLISS-B-I";
+    Spec::new(12289, 512, 10, 23, 215.0, 154, 0)
+        .b_inf(2100).b_l2(12872 * 12872).t_bits(12);
+    original 1.0, 12872 * 12872
 );
 
-#[cfg(feature = "ii")]
 bliss_param!(
-    12289,  512,    10,     24,     23,     1563,   11073 * 11073,
-    154,    0,      17825,  107.0,  2.18,
-    2,      3,      14,     12,     3,      9
+    /// BLISS-B-II, 128-bit security with a smaller sigma and more rejections.
+    BlissII = 2, "BLISS-B-II";
+    Spec::new(12289, 512, 10, 23, 107.0, 154, 0)
+        .b_inf(1563).b_l2(11073 * 11073).z_bits(3);
+    original 0.5, 11074 * 11074
 );
 
-#[cfg(feature = "iii")]
 bliss_param!(
-    12289,  512,    9,      48,     30,     1760,   10206 * 10206,
-    216,    16,     42270,  250.0,  1.40,
-    3,      4,      14,     12,     3,      9
+    /// BLISS-B-III, 160-bit security.
+    BlissIII = 3, "BLISS-B-III";
+    Spec::new(12289, 512, 9, 30, 250.0, 216, 16)
+        .b_inf(1760).b_l2(10206 * 10206);
+    original 0.7, 10206 * 10206
 );
 
-#[cfg(feature = "iv")]
 bliss_param!(
-    12289,  512,    8,      96,     39,     1613,   9901 * 9901,
-    231,    31,     69576,  271.0,  1.61,
-    3,      4,      14,     12,     4,      9
+    /// BLISS-B-IV, 192-bit security.
+    BlissIV = 4, "BLISS-B-IV";
+    Spec::new(12289, 512, 8, 39, 271.0, 231, 31)
+        .b_inf(1613).b_l2(9901 * 9901);
+    original 0.55, 9901 * 9901
 );
 
+bliss_param!(
+    /// BLISS-B-V, n = 1024 with 256-bit challenges, for long-lived keys.
+    BlissV = 5, "BLISS-B-V";
+    Spec::new(12289, 1024, 8, 44, 350.0, 308, 31)
+);
 
 #[cfg(feature = "o")]
-pub const W: [i32; 256] = [
-        1,  7146,  2028,  5722,  3449,  5906,  4862,  2689,
-     5413,  7463,  1415,  3394,  4607,   856,  2900,    62,
-     5235,  2840,  1438,  6451,  5165,  1885,  5417,  5323,
-     1846,  3239,  3041,  1437,  6986,  3137,  3844,  1968,
-     7098,  4665,   550,  5309,  1655,  5571,  7424,  6918,
-     1112,  4198,  4603,  2996,  2469,   217,  6801,  2259,
-     5033,  3376,  6556,  2757,  7438,  7109,  6461,  7496,
-     6803,  1189,  1408,  7139,  5773,  6888,  1800,  4806,
-     1925,  7060,  1952,   296,  2941,  1170,  3892,  7012,
-     4589,  2805,  4801,  4600,  4601,  4066,  6094,  4135,
-     7584,  5809,  2990,  5679,  3411,  3193,  4608,   321,
-     4928,  5784,  1003,  1065,  6300,  1459,  2897,  1667,
-     6832,  1036,  6453,  4095,  5941,  1499,  4540,  5977,
-     5282,   738,  4582,  6550,  5967,  2951,  3501,  1129,
-     2784,   674,   417,  7335,   766,  4964,  1886,  4882,
-     7351,  7568,  6688,  1266,  6299,  1994,   869,  3626,
-     3383,  2811,  1591,  1406,   528,  1717,  3125,  2583,
-      675,  7563,  1682,  6488,   732,   111,  2063,  2359,
-     5300,  6470,  2681,  2012,  6601,  1725,  6526,  3445,
-      365,  4431,  2844,  6979,  6882,  5010,   319,  5998,
-     1728,  4921,  1848,  2169,  7097,  5200,  6203,  7268,
-     5887,  7346,  2562,  4229,  3380,  4416,  3188,  7283,
-     5543,  7042,  3901,  2197,  7479,   536,  5118,  3987,
-     2273,  5224,  1044,  2173,  4957,  5631,  6048,  5702,
-     6468,  3751,  5637,  2838,  2508,  2395,  1402,  2668,
-     1286,  3280,  4149,    94,  3477,  6288,   198,  1604,
-     2132,  3849,  6974,  1876,  2551,  2433,  4115,  2922,
-     3654,  3765,  5828,   506,  5806,  4595,  7276,  1607,
-      527,  2252,  1097,  4542,  4907,  1657,  4501,  3799,
-     3000,   329,   648,  6646,   693,  5614,  7462,  1950,
-     1366,  6566,  5088,  4675,  2881,  2546,  5108,  1656,
-     5036,  1771,  4959,  4561,  2423,  1784,  5685,   201
-];
+bliss_param!(
+    /// BLISS-B-0, a toy set of at most 60-bit security, for tests and experiments.
+    /// It is INSECURE, never use it to protect anything.
+    Bliss0 = 0, "BLISS-B-0";
+    Spec::new(7681, 256, 5, 12, 100.0, 141, 39)
+        .b_inf(530).b_l2(2492 * 2492)
+);
 
-#[cfg(feature = "o")]
-pub const R: [i32; 256] = [
-       30,  6993,  7073,  2678,  3617,   517,  7602,  3860,
-     1089,  1141,  4045,  1967,  7633,  2637,  2509,  1860,
-     3430,   709,  4735,  1505,  1330,  2783,  1209,  6070,
-     1613,  4998,  6739,  4705,  2193,  1938,   105,  5273,
-     5553,  1692,  1138,  5650,  3564,  5829,  7652,   153,
-     2636,  3044,  7513,  5389,  4941,  6510,  4324,  6322,
-     5051,  1427,  4655,  5900,   391,  5883,  1805,  2131,
-     4384,  4946,  3835,  6783,  4208,  6934,   233,  5922,
-     3983,  4413,  4793,  1199,  3739,  4376,  1545,  2973,
-     7093,  7340,  5772,  7423,  7453,  6765,  6157,  1154,
-     4771,  5288,  5209,  1388,  2477,  3618,  7663,  1949,
-     1901,  4538,  7047,  1226,  4656,  5365,  2419,  3924,
-     5254,   356,  1565,  7635,  1567,  6565,  5623,  2647,
-     4840,  6778,  6883,  4475,  2347,  4039,  5177,  3146,
-     6710,  4858,  4829,  4982,  7618,  2981,  2813,   521,
-     5462,  4291,   934,  7256,  4626,  6053,  3027,  1246,
-     1637,  7520,  1644,  3775,   478,  5424,  1578,   680,
-     4888,  4141,  4374,  2615,  6598,  3330,   442,  1641,
-     5380,  2075,  3620,  6593,  6005,  5664,  3755,  3497,
-     3269,  2353,   829,  1983,  6754,  4361,  1889,  3277,
-     5754,  1691,  1673,  3622,  5523,  2380,  1746,  2972,
-     7628,  5312,    50,  3974,  1547,  1903,  3468,  3422,
-     4989,  3873,  1815,  4462,  1621,   718,  7601,  4395,
-     6742,  3100,   596,  3742,  2771,  7629,  4777,  2078,
-     2015,  4996,   128,   649,  6111,  2721,  3655,  3230,
-      175,  6228,  1574,  2820,  4457,  4296,  5940,  2034,
-     2512,   255,  1833,  2513,  7401,  3861,   554,  3169,
-     2086,  5416,  5858,  7499,  5198,  7273,  3212,  2124,
-      448,  6112,  2186,  5683,  1271,  3624,  4453,  6436,
-     5509,  2189,  4078,  7355,  5428,  7119,  1111,  4733,
-     2575,  4955,  6701,  1992,  1939,  7251,  7301,  3594,
-     5141,  7044,  2831,  6253,  3561,  7434,  1568,  6030
-];
-
-// Roots of unity. q = 12289  n = 512
-
-#[cfg(any(
-    feature = "i",
-    feature = "ii",
-    feature = "iii",
-    feature = "iv"
-))]
-pub const W: [i32; 512] = [
-        1, 10302,  3400,  3150,  8340,  6281,  5277,  9407,
-    12149,  7822,  3271,  1404, 12144,  5468, 10849, 10232,
-     7311, 10930,  9042,    64,  8011,  8687,  4976,  5333,
-     8736,  5925, 12176,  3329,  9048,   431,  3833,  3009,
-     5860,  6152,  3531,   922, 11336,  1105,  4096,  8855,
-     2963, 11239,  9509,  6099, 10530,  5057,  4143,  1489,
-     3006, 11821,  8241,  6370,   480,  4782,  9852,   453,
-     9275,  4075,  1426,  5297,  6534,  6415,  9377, 10314,
-     4134,  7083,  9273,  8049,  6915, 11286,  2143,  6142,
-    11112,  3789,  4414,  3728,  2731,  5241,  7205,   350,
-     5023, 10256,  8779,  6507, 10908,  3600, 11287,   156,
-     9542,  1973, 12129, 10695,  9005, 12138,  5101,  2738,
-     3621,  6427, 10111,  1958,  5067,  8851, 10911,  9928,
-     9198,  9606,  9984,  8527,  3382,  2049,  8585, 11026,
-     2625,  6950,  3186, 10542,  5791,  8076,  2422,  4774,
-     1170, 10120,  8653, 11089,   334, 12237,  5012,  7535,
-     8246,  8724,  5191,  8243,  2396,  7280, 11082,  1954,
-      726,  7540, 10600,  1146,  8652,   787,  9223,  9087,
-     8961,  1254,  2969, 11606,  5331,   421, 11414,  5876,
-    11227,  8775,  2166,  9597,  3289,  2505, 11899,   723,
-     1212,   400,  3985,  8210,  6522,  5681,  5444,  9381,
-     2366,  5445,  7394,  5766,  8595,  3445, 12047,  1583,
-      563, 11907,  9405,  3834,  1022,  9260,  9302, 11871,
-     7203,  4324, 10512,  3956,  4388,  6234,   354,  9364,
-    11567,  9090,  3000, 11454,   130, 12048, 11885,  3963,
-     2768,  5456, 10115,  6299,  6378,  9162,  7404, 10474,
-     5728, 10367,  9424,  2948,  4177,  7665,  8005,  8320,
-     9154, 11011,  7852,  5106,  5092,  8332,  9888,  2655,
-     8785,  6874,  6730, 10211, 12171,   975,  4337,  9259,
-    11289,  8471,  4053,  8273,  4231, 10968,  7270,  6374,
-     4821,  6093, 10163,  9235,  9821,   605,  2187,  4737,
-      955,  7210,  2704,  9734,  1428,  1323,  1045,   426,
-     1479, 10587,  2399,  1319,  8993, 11404,  1168,  1805,
-     1853,  4789,  8232, 11964,  6747,  1010,  8526,  5369,
-    10938,  5435,  2686,  8633,  1673,  6068, 10682, 10258,
-     4805,  1018,  4919,  7991, 11560, 10710,  3778,  1693,
-     3195,  4948, 11813, 11848,  3748, 12147, 11796,  8760,
-     7393,  7753,  5195,   295,  3707,  7591,  7575,  2500,
-     9545,  8301, 10040,  7856,  9447,  6403,  8643,  6381,
-     3201,  5315,  7635,  6170,  4632,   677,  6591,  3757,
-     6553,  5529,   243,  8719,  2837,  3532, 11224,  2447,
-     4255,   147,  2847,  8240,  8357,  9369,  1632,  1512,
-     6461,  3998,  6957,  1566,  9764,  3263,  5011,  9522,
-     4846,  5574,  9140,  1962,  9408, 10162, 11222,  6421,
-     9744,  6136, 10745,  7967, 10092,  2844,  1912, 10446,
-    12208,  1190,  7247,  2919,   355,  7377,  2678, 12240,
-    11340,  5446,  5407,  9166, 11745, 11785,  6039,  6860,
-     9970, 11767,  4938,  7105,  2426,  9115,  2481, 10431,
-     5146, 11635,  9153,   709,  4452,  1956,  9041,  2051,
-     4611,  5537,  8925, 11341,  3459,  8807,    27,  7796,
-     5777, 11316,  3978,  9830,  7300,  8209,  8509,  2281,
-     2294,  1041,  8374,   168, 10276,  5906,   773,   174,
-    10643,  1728,  7384,  1058, 11462,  8812,  2381,   218,
-     9238,  3860, 10805, 11637,  5179,  7509, 10752,  6347,
-     9314,   316, 11136,  5257, 12280,  5594,  6267,  8517,
-    10963,  4916,  1663,  1360,  1260,  3336,  7428, 11942,
-     1305, 12233,   671,  6224,  7935, 12231,  4645, 11713,
-     1635,  7840,  4372,  1159,  7399,  8120,  1017,  6906,
-     4591,  8410,  2370,  9786,  8705,  6077,  5088,  3991,
-     8577,  2344,     3,  6328, 10200,  9450,   442,  6554,
-     3542,  3643, 11869, 11177,  9813,  4212, 11854,  4115,
-     7969,  6118,  9644,  8212,  2548,   192, 11744,  1483,
-     2639,  3710,  1630,  5486, 11950,  9987,  2566,  1293,
-    11499,  9027,  5291,  6167, 10593,  2766,  9430,  3315
-];
-
-#[cfg(any(
-    feature = "i",
-    feature = "ii",
-    feature = "iii",
-    feature = "iv"
-))]
-pub const R: [i32; 512] = [
-       24,  1468,  7866,  1866,  3536,  3276,  3758,  4566,
-     8929,  3393,  4770,  9118,  8809,  8342,  2307, 12077,
-     3418,  4251,  8095,  1536,  7929, 11864,  8823,  5102,
-      751,  7021,  9577,  6162,  8239, 10344,  5969, 10771,
-     5461,   180, 11010,  9839,  1706,  1942, 12281,  3607,
-     9667, 11667,  7014, 11197,  6940, 10767,  1120, 11158,
-    10699,  1057,  1160,  5412, 11520,  4167,  2957, 10872,
-     1398, 11777,  9646,  4238,  9348,  6492,  3846,  1756,
-      904, 10235,  1350,  8841,  6203,   506,  2276, 12229,
-     8619,  4913,  7624,  3449,  4099,  2894,   874,  8400,
-     9951,   364,  1783,  8700,  3723,   377,   530,  3744,
-     7806, 10485,  8449, 10900,  7207,  8665, 11823,  4267,
-      881,  6780,  9173, 10125, 11007,  3511,  3795,  4781,
-    11839,  9342,  6125,  8024,  7434,    20,  9416,  6555,
-     1555,  7043,  2730,  7228,  3805,  9489,  8972,  3975,
-     3502,  9389, 11048,  8067,  8016, 11041,  9687,  8794,
-     1280,   463,  1694,  1208,  8348,  2674,  7899, 10029,
-     5135,  8914,  8620,  2926, 11024,  6599,   150,  9175,
-     6151,  5518,  9811,  8186,  5054, 10104,  3578,  5845,
-    11379,  1687,  2828,  9126,  5202, 10964,  2929,  5063,
-     4510,  9600,  9617,   416,  9060,  1165,  7766,  3942,
-     7628,  7790,  5410,  3205,  9656,  8946,  6481,  1125,
-     1223,  3121,  4518,  5993, 12239,  1038,  2046,  2257,
-      826,  5464,  6508,  8921,  7000,  2148,  8496,  3534,
-     7250,  9247, 10555,  4538,  3120,  6505,  2593,  9089,
-     4987,  8054,  9269,  3708,  5604, 10975,  5650,  5596,
-     2293,  3028,  4974,  9307,  1936, 11914,  7785,  3056,
-    10783,  6195,  4113, 11943, 11607,  3344,  3821,  2275,
-     1927,  5219,  1763, 11573,  9457, 11111,  5776,  1014,
-      578,  6680, 11249,  1928,  3232,  5163,  2434,  5508,
-     5103, 11053, 10421,   438,  2213,  2231,  3332,  3087,
-    10631,   994,  3451,   125,  9694,  7174,   502, 10224,
-    10918,  8308,  8420,  7078,  6919,  3338,  3454,  6453,
-     7605,  4335,   944,  4489,  2171, 11951,  8000,  5966,
-     4443,  7550,  3019, 10568,  3285, 10453, 10588,   412,
-     4719, 12143,  7455,  7449,  7082, 11260,  4649,  3765,
-     2946,  8151,   865,  1705,  3929,  8881,   457,  1327,
-     5386,  1737,  1790,  7080,  2945, 10138,  9754, 10844,
-     7878,  2600,  7469,  4209,  5526,  6204, 10808,  5676,
-     3090,  4670, 11194,   612,   567,  3959, 10716,  4145,
-     9804,  9806,  5832,   343,  6643, 11034, 11307,  9572,
-     3808,  3528,  6883,  1136,  3944,  3654,  2301, 11710,
-     7596,  9929,  7211,   717,   845,  4578,  9663,  7326,
-     5703, 10886, 10447, 10221,  4590, 10397, 11259,  6636,
-      365, 12085, 12100,  6873,  8717,  6811,  9021,  4924,
-    10345,  3982,  1882,  8611,  8520,  5002,  2827, 11113,
-     1802,  7814,  6878, 11071, 11522,   193,  9757,  4883,
-     5789, 12050,  7911, 10763,  9068,  9847, 10388,  4564,
-      614,  8882, 10759,  4727,  8536, 10077,  8071,    68,
-       63,  9998,  5287,  1826,  9282,  2455,   648,  2769,
-     3469,  1226,  9449,  2429,  3154,   392,  7592,  5588,
-     5900,   406,  4352,  4032,   844,  6565,  6263,  4176,
-     9652,  4605,  5170,   814,  4730,  2575,  7988,  5232,
-      510,  6617,  1251,  8930,  1406,  8170, 12268,  4860,
-     2334,  7584,  9195,  3278, 12073, 11366,  2940,  7784,
-     5043,  7383,  3045,  8062,  5662,  6330,  6226,  3961,
-     6742, 10945,  3815,  1908,  6105, 10897,   879, 10754,
-     2373,  3825,  6616,  3238,  5530, 10545, 12119,  5987,
-    11872,  5216,  7724,  1373,     7, 10669, 11511,  9761,
-     9224,  7100,    72,  4404, 11309,  5598, 10608,  9828,
-    11274,  1409,  2209, 10179,  2021,  2776,  1849,   448,
-     6921, 11653, 10254,   464, 11996,  4608, 11498, 11014,
-     1891,  3017,  2253,  8774,  4153,  6197,   139,  6454,
-     5618,  7735,  4094,   540,  8452,  4939,  5118,  5826
-];
+
+#[test]
+fn test_derive_published() {
+    // P, PMAX, M, widths and K_SIGMA of the BLISS-B paper
+    fn check<P: ParameterSet>(p: i32, pmax: i32, m: f64, widths: [usize; 6], k_sigma: i32) {
+        assert_eq!(P::P, p, "{}", P::NAME);
+        assert_eq!(P::PMAX, pmax, "{}", P::NAME);
+        assert!((P::M / m - 1.0).abs() < 0.01, "{}", P::NAME);
+        assert_eq!([P::F_BITS, P::G_BITS, P::A_BITS, P::T_BITS, P::Z_BITS, P::CIDX_BITS], widths, "{}", P::NAME);
+        assert_eq!(P::K_SIGMA, k_sigma, "{}", P::NAME);
+        assert_eq!(P::W.len(), P::N);
+        assert_eq!(P::EXP_TABLE.last().map(|&x| x != 0), Some(true));
+        assert!(P::PMAX as u64 >> P::EXP_TABLE.len() == 0);
+    }
+
+    check::<BlissI>(24, 17825, 1.21, [2, 3, 14, 12, 3, 9], 254);
+    check::<BlissII>(24, 17825, 2.18, [2, 3, 14, 12, 3, 9], 126);
+    check::<BlissIII>(48, 42270, 1.40, [3, 4, 14, 12, 3, 9], 295);
+    check::<BlissIV>(96, 69576, 1.61, [3, 4, 14, 12, 4, 9], 320);
+    #[cfg(feature = "o")]
+    check::<Bliss0>(480, 17928, 2.44, [3, 4, 13, 11, 6, 8], 118);
+}
+
+#[test]
+fn test_derive_original() {
+    // M of the BLISS paper
+    fn check<P: OriginalSet>(m: f64) {
+        assert!((P::M_ORIGINAL / m - 1.0).abs() < 0.01, "{}", P::NAME);
+        assert!(P::PMAX_ORIGINAL > P::PMAX);
+    }
+
+    check::<BlissI>(1.65);
+    check::<BlissII>(7.39);
+    check::<BlissIII>(2.77);
+    check::<BlissIV>(5.22);
+}
+
+#[test]
+fn test_derive_unpinned() {
+    let params = Spec::new(12289, 512, 10, 23, 215.0, 154, 0).derive();
+    assert_eq!(params.t_bits, 13);
+    assert_eq!(params.b_inf, 2099);
+    assert!((params.b_l2 as f64).sqrt() / 12872.0 - 1.0 < 0.01);
+    assert_eq!(w_table::<512>(12289)[1], 10302);
+}
+
+#[test]
+#[should_panic(expected = "q must be 1 modulo 2n")]
+fn test_derive_inconsistent() {
+    Spec::new(7681, 1024, 5, 12, 100.0, 141, 39).derive();
+}
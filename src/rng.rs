@@ -0,0 +1,97 @@
+use std::cmp::min;
+use rand::{ Rng, OsRng, ChaChaRng, StdRng, ThreadRng };
+use tiny_keccak::Keccak;
+use byteorder::{ LittleEndian, ByteOrder };
+use ::utils::{ scrub, scrub_value };
+
+
+/// Marker for random number generators fit for key generation and signing.
+pub trait CryptoRng: Rng {}
+
+impl CryptoRng for OsRng {}
+impl CryptoRng for ChaChaRng {}
+impl CryptoRng for StdRng {}
+impl CryptoRng for ThreadRng {}
+impl<R: CryptoRng + ?Sized> CryptoRng for &mut R {}
+
+
+/// SHAKE256 output stream over the concatenated inputs,
+/// words are read in little endian so the stream is the same on every platform.
+/// The sponge is squeezed in place a block at a time rather than moved into an
+/// `XofReader`, so that the one copy of the absorbed seed or key is scrubbed on drop.
+pub struct ShakeRng {
+    shake: Keccak,
+    block: [u8; SHAKE256_RATE],
+    offset: usize
+}
+
+const SHAKE256_RATE: usize = 136;
+
+impl ShakeRng {
+    pub fn new(input: &[&[u8]]) -> ShakeRng {
+        let mut shake = Keccak::new_shake256();
+        for x in input {
+            shake.update(x);
+        }
+        shake.pad();
+        shake.keccakf();
+        ShakeRng { shake, block: [0; SHAKE256_RATE], offset: SHAKE256_RATE }
+    }
+}
+
+impl Rng for ShakeRng {
+    fn next_u32(&mut self) -> u32 {
+        let mut buf = [0; 4];
+        self.fill_bytes(&mut buf);
+        LittleEndian::read_u32(&buf)
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut buf = [0; 8];
+        self.fill_bytes(&mut buf);
+        LittleEndian::read_u64(&buf)
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        let mut i = 0;
+        while i < dest.len() {
+            if self.offset == SHAKE256_RATE {
+                // a full block is squeezed from the start and the state permuted
+                self.shake.squeeze(&mut self.block);
+                self.offset = 0;
+            }
+            let len = min(dest.len() - i, SHAKE256_RATE - self.offset);
+            dest[i..i + len].copy_from_slice(&self.block[self.offset..self.offset + len]);
+            self.offset += len;
+            i += len;
+        }
+    }
+}
+
+impl Drop for ShakeRng {
+    fn drop(&mut self) {
+        unsafe { scrub_value(&mut self.shake) };
+        scrub(&mut self.block);
+    }
+}
+
+impl CryptoRng for ShakeRng {}
+
+#[test]
+fn test_shake_rng_stream() {
+    // the same stream as an `XofReader`, across block boundaries and uneven reads
+    let mut xof = {
+        let mut shake = Keccak::new_shake256();
+        shake.update(b"blissb");
+        shake.update(b"shake");
+        shake.xof()
+    };
+    let mut rng = ShakeRng::new(&[b"blissb", b"shake"]);
+
+    for len in (0..64).chain(130..140) {
+        let (mut x, mut y) = ([0; 140], [0; 140]);
+        xof.squeeze(&mut x[..len]);
+        rng.fill_bytes(&mut y[..len]);
+        assert_eq!(&x[..], &y[..]);
+    }
+}
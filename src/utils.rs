@@ -1,14 +1,23 @@
 use std::cmp::max;
+use std::{ mem, ptr, slice };
+use std::sync::atomic::{ compiler_fence, Ordering };
 use rand::Rng;
 use tiny_keccak::Keccak;
 use byteorder::{ BigEndian, ByteOrder };
-use ::param::{ N, NZ1, NZ2, KAPPA };
+use ::param::{ ParameterSet, Array };
 
 
 #[inline]
-pub fn vecabsmax(v: &[i32]) -> i32 {
+pub fn vecabsmax(v: &[i32]) -> i64 {
     v.iter()
-        .fold(0, |sum, next| max(sum, next.abs()))
+        .fold(0, |sum, &next| max(sum, (next as i64).abs()))
+}
+
+#[inline]
+pub fn vecnorm(v: &[i32]) -> i64 {
+    v.iter()
+        .map(|&vi| vi as i64 * vi as i64)
+        .sum()
 }
 
 #[inline]
@@ -19,13 +28,28 @@ pub fn vecscalar(t: &[i32], u: &[i32]) -> i32 {
         .sum()
 }
 
-pub fn uniform_poly(v: &mut [i32], rng: &mut Rng) {
-    v.copy_from_slice(&[0; N]);
+/// Overwrite `v` with zeros, in a way the optimizer will not remove.
+pub fn scrub<T: Copy + Default>(v: &mut [T]) {
+    for x in v.iter_mut() {
+        unsafe { ptr::write_volatile(x, T::default()) };
+    }
+    compiler_fence(Ordering::SeqCst);
+}
+
+/// `scrub` for a value with no access to its fields, such as a `Keccak` state.
+/// Unsafe since all zero bytes must be a valid `T`.
+pub unsafe fn scrub_value<T>(x: &mut T) {
+    scrub(slice::from_raw_parts_mut(x as *mut T as *mut u8, mem::size_of::<T>()));
+}
+
+pub fn uniform_poly<P: ParameterSet>(v: &mut [i32], rng: &mut dyn Rng) {
+    let n = P::N;
+    v.fill(0);
 
     let mut i = 0;
-    while i < NZ1 {
+    while i < P::NZ1 {
         let x = rng.next_u64();
-        let j = (x >> 1) as usize % N;
+        let j = (x >> 1) as usize % n;
         if v[j] == 0 {
             v[j] = if x & 1 != 0 { 1 } else { -1 };
             i += 1;
@@ -33,9 +57,9 @@ pub fn uniform_poly(v: &mut [i32], rng: &mut Rng) {
     }
 
     let mut i = 0;
-    while i < NZ2 {
+    while i < P::NZ2 {
         let x = rng.next_u64();
-        let j = (x >> 1) as usize % N;
+        let j = (x >> 1) as usize % n;
         if v[j] == 0 {
             v[j] = if x & 1 != 0 { 2 } else { -2 };
             i += 1;
@@ -43,17 +67,18 @@ pub fn uniform_poly(v: &mut [i32], rng: &mut Rng) {
     }
 }
 
-pub fn c_oracle(c_idx: &mut [usize], hash: &[u8], w: &[i32]) -> bool {
-    let mut fl = [false; N];
+pub fn c_oracle<P: ParameterSet>(c_idx: &mut [usize], hash: &[u8], w: &[i32]) -> bool {
+    let n = P::N;
+    let mut fl = P::Poly::zero();
     let mut idx_i = 0;
-    for r in 0..::std::u16::MAX {
+    for r in 0..u16::MAX {
         let mut sha3 = Keccak::new_sha3_512();
         sha3.update(hash);
 
         let mut output = [0; 64];
         let mut t = [0; 2];
-        for i in 0..N {
-            BigEndian::write_u16(&mut t, w[i] as u16);
+        for &wi in &w[..n] {
+            BigEndian::write_u16(&mut t, wi as u16);
             sha3.update(&t);
         }
         BigEndian::write_u16(&mut t, r);
@@ -61,12 +86,16 @@ pub fn c_oracle(c_idx: &mut [usize], hash: &[u8], w: &[i32]) -> bool {
         sha3.finalize(&mut output);
 
         for i in (0..64).step_by(2) {
-            let idx = BigEndian::read_u16(&output[i..]) as usize % N;
-            if !fl[idx] {
+            let idx = BigEndian::read_u16(&output[i..]) as usize % n;
+            if fl[idx] == 0 {
                 c_idx[idx_i] = idx;
                 idx_i += 1;
-                if idx_i == KAPPA { return true };
-                fl[idx] = true;
+                if idx_i == P::KAPPA {
+                    // sorted, so that a signature has a single encoding
+                    c_idx.sort_unstable();
+                    return true;
+                }
+                fl[idx] = 1;
             }
         }
     }
@@ -74,38 +103,386 @@ pub fn c_oracle(c_idx: &mut [usize], hash: &[u8], w: &[i32]) -> bool {
     false
 }
 
-pub fn greedy_sc(f: &[i32], g: &[i32], c_idx: &[usize], x: &mut [i32], y: &mut [i32]) {
-    x.copy_from_slice(&[0; N]);
-    y.copy_from_slice(&[0; N]);
+/// The dense `GreedySC`, the reference of `greedy_sc_table`.
+#[cfg(test)]
+pub fn greedy_sc<P: ParameterSet>(f: &[i32], g: &[i32], c_idx: &[usize], x: &mut [i32], y: &mut [i32]) {
+    let n = P::N;
+    x.fill(0);
+    y.fill(0);
 
     for &i in c_idx {
         let mut sgn = 0;
 
-        for j in 0..(N - i) {
+        for j in 0..(n - i) {
             sgn += f[j] * x[i + j] + g[j] * y[i + j];
         }
-        for j in (N - i)..N {
-            sgn -= f[j] * x[i + j - N] + g[j] * y[i + j - N];
+        for j in (n - i)..n {
+            sgn -= f[j] * x[i + j - n] + g[j] * y[i + j - n];
         }
 
-        if sgn > 0 {
-            for j in 0..(N - i) {
-                x[i + j] -= f[j];
-                y[i + j] -= g[j];
+        // -1 if sgn > 0 else 1, without branching on the secret
+        let s = ((-sgn) >> 31) | 1;
+
+        for j in 0..(n - i) {
+            x[i + j] += s * f[j];
+            y[i + j] += s * g[j];
+        }
+        for j in (n - i)..n {
+            x[i + j - n] -= s * f[j];
+            y[i + j - n] -= s * g[j];
+        }
+    }
+}
+
+/// The autocorrelation of `f` and `g`, kept with a private key for `greedy_sc_table`,
+/// and without `ct` their nonzero positions, which the autocorrelation is built over.
+/// With `ct` no memory access may depend on the key, so there are no positions
+/// and the autocorrelation is built over every position.
+pub struct Sparse<P: ParameterSet> {
+    #[cfg(not(feature = "ct"))]
+    f_idx: P::Support,
+    #[cfg(not(feature = "ct"))]
+    g_idx: P::Support,
+    #[cfg(not(feature = "ct"))]
+    f_len: usize,
+    #[cfg(not(feature = "ct"))]
+    g_len: usize,
+    rot: P::Poly
+}
+
+impl<P: ParameterSet> Sparse<P> {
+    pub fn zero() -> Sparse<P> {
+        Sparse {
+            #[cfg(not(feature = "ct"))]
+            f_idx: P::Support::zero(),
+            #[cfg(not(feature = "ct"))]
+            g_idx: P::Support::zero(),
+            #[cfg(not(feature = "ct"))]
+            f_len: 0,
+            #[cfg(not(feature = "ct"))]
+            g_len: 0,
+            rot: P::Poly::zero()
+        }
+    }
+
+    pub fn fill(&mut self, f: &[i32], g: &[i32]) {
+        #[cfg(feature = "ct")]
+        autocorrelation::<P>(f, g, self.rot.as_mut());
+
+        // S_a S_b adds to <S, x^k S> for b - a = k, and subtracts for b - a = k - n
+        #[cfg(not(feature = "ct"))]
+        {
+            let n = P::N;
+            self.f_len = support(f, self.f_idx.as_mut());
+            self.g_len = support(g, self.g_idx.as_mut());
+            let rot = &mut self.rot.as_mut()[..n];
+            rot.fill(0);
+            for &(v, idx) in &[(f, &self.f_idx.as_ref()[..self.f_len]), (g, &self.g_idx.as_ref()[..self.g_len])] {
+                for &a in idx {
+                    for &b in idx {
+                        let (a, b) = (a as usize, b as usize);
+                        if b >= a { rot[b - a] += v[a] * v[b] } else { rot[b + n - a] -= v[a] * v[b] };
+                    }
+                }
             }
-            for j in (N - i)..N {
-                x[i + j - N] += f[j];
-                y[i + j - N] += g[j];
+        }
+    }
+}
+
+impl<P: ParameterSet> Drop for Sparse<P> {
+    fn drop(&mut self) {
+        #[cfg(not(feature = "ct"))]
+        {
+            scrub(self.f_idx.as_mut());
+            scrub(self.g_idx.as_mut());
+        }
+        scrub(self.rot.as_mut());
+    }
+}
+
+/// Write the positions of the nonzero coefficients of `v` to `idx`, in increasing order,
+/// and return their number.
+#[cfg(not(feature = "ct"))]
+fn support(v: &[i32], idx: &mut [u16]) -> usize {
+    let mut len = 0;
+    for (j, &x) in v.iter().enumerate() {
+        if x != 0 {
+            idx[len] = j as u16;
+            len += 1;
+        }
+    }
+    len
+}
+
+/// The negacyclic autocorrelation of `S = (f, g)`, `t[k] = <S, x^k S>`.
+pub fn autocorrelation<P: ParameterSet>(f: &[i32], g: &[i32], t: &mut [i32]) {
+    let n = P::N;
+
+    for k in 0..n {
+        let mut x = 0;
+        for j in 0..(n - k) {
+            x += f[j] * f[j + k] + g[j] * g[j + k];
+        }
+        for j in (n - k)..n {
+            x -= f[j] * f[j + k - n] + g[j] * g[j + k - n];
+        }
+        t[k] = x;
+    }
+}
+
+/// `greedy_sc` with the same output. Since `<x^i S, x^j S> = rot[|i - j|]`, the sign of each
+/// rotation takes `KAPPA` lookups in the autocorrelation instead of `2N` products.
+/// The lookups depend on `c_idx` only. `x, y` are updated over the nonzero positions of `f, g`,
+/// or over every position with `ct`, so that the addresses do not reveal the support.
+pub fn greedy_sc_table<P: ParameterSet>(f: &[i32], g: &[i32], sparse: &Sparse<P>, c_idx: &[usize],
+    x: &mut [i32], y: &mut [i32])
+{
+    let n = P::N;
+    let rot = &sparse.rot.as_ref()[..n];
+    x.fill(0);
+    y.fill(0);
+
+    // 1 where the rotation was subtracted
+    let mut neg = P::Index::zero();
+
+    for (k, &i) in c_idx.iter().enumerate() {
+        let mut sgn = 0;
+        for (l, &j) in c_idx[..k].iter().enumerate() {
+            let m = -(neg[l] as i32);
+            let r = rot[i.abs_diff(j)];
+            sgn += (r ^ m) - m;
+        }
+
+        // -1 if sgn > 0 else 1, without branching on the secret
+        let s = ((-sgn) >> 31) | 1;
+        neg[k] = (((-sgn) >> 31) & 1) as usize;
+
+        #[cfg(feature = "ct")]
+        {
+            for j in 0..(n - i) {
+                x[i + j] += s * f[j];
+                y[i + j] += s * g[j];
             }
-        } else {
-            for j in 0..(N - i) {
-                x[i + j] += f[j];
-                y[i + j] += g[j];
+            for j in (n - i)..n {
+                x[i + j - n] -= s * f[j];
+                y[i + j - n] -= s * g[j];
             }
-            for j in (N - i)..N {
-                x[i + j - N] -= f[j];
-                y[i + j - N] -= g[j];
+        }
+
+        #[cfg(not(feature = "ct"))]
+        {
+            add_rotation(x, f, &sparse.f_idx.as_ref()[..sparse.f_len], i, s, n);
+            add_rotation(y, g, &sparse.g_idx.as_ref()[..sparse.g_len], i, s, n);
+        }
+    }
+
+    scrub(neg.as_mut());
+}
+
+/// `w += s x^i v`, with `idx` the nonzero positions of `v`.
+#[cfg(not(feature = "ct"))]
+fn add_rotation(w: &mut [i32], v: &[i32], idx: &[u16], i: usize, s: i32, n: usize) {
+    for &j in idx {
+        let j = j as usize;
+        if i + j < n { w[i + j] += s * v[j] } else { w[i + j - n] -= s * v[j] };
+    }
+}
+
+/// `x = f c` and `y = g c`, the plain product of the original BLISS.
+pub fn plain_sc<P: ParameterSet>(f: &[i32], g: &[i32], c_idx: &[usize], x: &mut [i32], y: &mut [i32]) {
+    let n = P::N;
+    x.fill(0);
+    y.fill(0);
+
+    for &i in c_idx {
+        for j in 0..(n - i) {
+            x[i + j] += f[j];
+            y[i + j] += g[j];
+        }
+        for j in (n - i)..n {
+            x[i + j - n] -= f[j];
+            y[i + j - n] -= g[j];
+        }
+    }
+}
+
+/// `N_kappa(S)`, the sum of the `KAPPA` largest rows of the Gram matrix of `S = (f, g)`,
+/// a row counting the sum of its `KAPPA` largest entries.
+/// The entry `(i, j)` is `<x^i S, x^j S>`, the autocorrelation at `|i - j|`.
+pub fn n_kappa<P: ParameterSet>(f: &[i32], g: &[i32]) -> i32 {
+    let (n, kappa) = (P::N, P::KAPPA);
+    let (mut t, mut row, mut sums) = (P::Poly::zero(), P::Poly::zero(), P::Poly::zero());
+    autocorrelation::<P>(f, g, t.as_mut());
+
+    for i in 0..n {
+        for j in 0..n {
+            row[j] = t[i.abs_diff(j)];
+        }
+        sums[i] = largest(row.as_mut(), kappa);
+    }
+    let result = largest(sums.as_mut(), kappa);
+
+    scrub(t.as_mut());
+    scrub(row.as_mut());
+    scrub(sums.as_mut());
+    result
+}
+
+/// The sum of the `k` largest entries of `v`, which is reordered.
+fn largest(v: &mut [i32], k: usize) -> i32 {
+    v.select_nth_unstable_by(k - 1, |x, y| y.cmp(x));
+    v[..k].iter().sum()
+}
+
+
+#[test]
+fn test_scrub() {
+    let mut v = [7; 512];
+    scrub(&mut v);
+    assert!(v.iter().all(|&x| x == 0));
+}
+
+/// `N_kappa` from the Gram matrix of the explicit rotations `x^i S`.
+#[cfg(test)]
+fn n_kappa_dense<P: ParameterSet>(f: &[i32], g: &[i32]) -> i32 {
+    let n = P::N;
+    let rotate = |v: &[i32], i: usize| (0..n)
+        .map(|k| if k >= i { v[k - i] } else { -v[k + n - i] })
+        .collect::<Vec<_>>();
+    let columns = (0..n).map(|i| (rotate(f, i), rotate(g, i))).collect::<Vec<_>>();
+
+    let mut gram = vec![vec![0; n]; n];
+    for i in 0..n {
+        for j in i..n {
+            gram[i][j] = vecscalar(&columns[i].0, &columns[j].0) + vecscalar(&columns[i].1, &columns[j].1);
+            gram[j][i] = gram[i][j];
+        }
+    }
+
+    let mut sums = gram.into_iter()
+        .map(|mut row| {
+            row.sort_unstable_by(|x, y| y.cmp(x));
+            row[..P::KAPPA].iter().sum::<i32>()
+        })
+        .collect::<Vec<_>>();
+    sums.sort_unstable_by(|x, y| y.cmp(x));
+    sums[..P::KAPPA].iter().sum()
+}
+
+#[cfg(test)]
+fn check_n_kappa<P: ParameterSet>() {
+    use ::bliss::PrivateKey;
+
+    let sk = PrivateKey::<P>::from_seed(&[P::ID; 32]).unwrap();
+    assert_eq!(n_kappa::<P>(sk.f().as_ref(), sk.g().as_ref()), n_kappa_dense::<P>(sk.f().as_ref(), sk.g().as_ref()));
+}
+
+#[test]
+fn test_n_kappa() {
+    check_n_kappa::<::param::BlissI>();
+    check_n_kappa::<::param::BlissIV>();
+    #[cfg(feature = "o")]
+    check_n_kappa::<::param::Bliss0>();
+}
+
+#[cfg(test)]
+fn check_greedy_sc_table<P: ParameterSet>() {
+    use rand::{ ChaChaRng, SeedableRng, Rng };
+
+    let n = P::N;
+    let mut rng = ChaChaRng::from_seed(&[4]);
+    let (mut f, mut g) = (P::Poly::zero(), P::Poly::zero());
+    let (mut x, mut y, mut x2, mut y2) = (P::Poly::zero(), P::Poly::zero(), P::Poly::zero(), P::Poly::zero());
+    let (mut sparse, mut t) = (Sparse::<P>::zero(), P::Poly::zero());
+    let mut c_idx = P::Index::zero();
+
+    for round in 0..64 {
+        uniform_poly::<P>(f.as_mut(), &mut rng);
+        uniform_poly::<P>(g.as_mut(), &mut rng);
+        for i in 0..n {
+            g[i] *= 2;
+        }
+        g[0] -= 1;
+
+        for k in 0..P::KAPPA {
+            c_idx[k] = loop {
+                let i = rng.gen_range(0, n);
+                if !c_idx.as_ref()[..k].contains(&i) { break i };
+            };
+        }
+
+        // the ends of the rotations, and dense polynomials
+        if round == 0 {
+            f[0] = 2; f[n - 1] = -2; g[n - 1] = 4;
+            c_idx[0] = 0; c_idx[1] = n - 1;
+        }
+        if round == 1 {
+            for i in 0..n {
+                f[i] = rng.gen_range(-2, 3);
+                g[i] = rng.gen_range(-4, 5);
             }
         }
+
+        sparse.fill(f.as_ref(), g.as_ref());
+        #[cfg(not(feature = "ct"))]
+        {
+            assert!(round < 2 || sparse.f_len == (P::NZ1 + P::NZ2) as usize);
+            assert!(sparse.g_idx.as_ref()[..sparse.g_len].iter().all(|&j| g[j as usize] != 0));
+        }
+        autocorrelation::<P>(f.as_ref(), g.as_ref(), t.as_mut());
+        assert_eq!(sparse.rot.as_ref(), t.as_ref());
+
+        greedy_sc::<P>(f.as_ref(), g.as_ref(), c_idx.as_ref(), x.as_mut(), y.as_mut());
+        greedy_sc_table::<P>(f.as_ref(), g.as_ref(), &sparse, c_idx.as_ref(), x2.as_mut(), y2.as_mut());
+        assert_eq!(x.as_ref(), x2.as_ref());
+        assert_eq!(y.as_ref(), y2.as_ref());
     }
 }
+
+#[test]
+fn test_greedy_sc_table() {
+    all_sets!(P => check_greedy_sc_table::<P>());
+}
+
+#[cfg(feature = "ct")]
+#[test]
+#[ignore]
+fn test_greedy_sc_leakage() {
+    use rand::{ ChaChaRng, SeedableRng };
+    use ::param::BlissI as P;
+
+    let mut rng = ChaChaRng::from_seed(&[1]);
+    let (mut x, mut y) = (<P as ParameterSet>::Poly::zero(), <P as ParameterSet>::Poly::zero());
+    let mut c_idx = <P as ParameterSet>::Index::zero();
+    for (i, c) in c_idx.as_mut().iter_mut().enumerate() {
+        *c = i * (P::N / P::KAPPA);
+    }
+
+    // a fixed secret against a pool of random ones
+    let keys = (0..64)
+        .map(|_| {
+            let (mut f, mut g) = (<P as ParameterSet>::Poly::zero(), <P as ParameterSet>::Poly::zero());
+            uniform_poly::<P>(f.as_mut(), &mut rng);
+            uniform_poly::<P>(g.as_mut(), &mut rng);
+            let mut sparse = Sparse::<P>::zero();
+            sparse.fill(f.as_ref(), g.as_ref());
+            (f, g, sparse)
+        })
+        .collect::<Vec<_>>();
+
+    let mut k = 0;
+    let t = ::dudect::leakage(1 << 16, |class| {
+        let (f, g, _) = if class { k = (k + 1) % keys.len(); &keys[k] } else { &keys[0] };
+        greedy_sc::<P>(f.as_ref(), g.as_ref(), c_idx.as_ref(), x.as_mut(), y.as_mut());
+    });
+    assert!(t.abs() < 10.0, "t = {}", t);
+
+    {
+        let t = ::dudect::leakage(1 << 16, |class| {
+            let (f, g, ref sparse) = if class { k = (k + 1) % keys.len(); &keys[k] } else { &keys[0] };
+            greedy_sc_table::<P>(f.as_ref(), g.as_ref(), sparse, c_idx.as_ref(), x.as_mut(), y.as_mut());
+        });
+        assert!(t.abs() < 10.0, "t = {}", t);
+    }
+}
+
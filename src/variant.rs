@@ -0,0 +1,63 @@
+use std::fmt;
+use ::param::{ ParameterSet, OriginalSet };
+use ::utils::{ Sparse, greedy_sc_table, plain_sc, n_kappa };
+
+
+/// The signing scheme over a parameter set, BLISS-B or the original BLISS.
+/// Both share the NTT, the oracle and the encodings.
+pub trait Variant<P: ParameterSet>: Copy + Default + fmt::Debug + Send + Sync + 'static {
+    const NAME: &'static str;
+    /// Separates the keys of `from_seed` from those of other variants.
+    const KEYGEN_TAG: &'static [u8];
+
+    /// Bound on `|Sc|^2`, with `M = exp(PMAX / (2 sigma^2))`.
+    const PMAX: i32;
+    const B_L2: i32;
+
+    /// Key generation keeps `(f, g)` only if this holds.
+    fn accept_key(f: &[i32], g: &[i32]) -> bool;
+
+    /// `x, y = Sc` for the challenge `c_idx`, up to the sign of each rotation,
+    /// where `sparse` is built from `f` and `g`.
+    fn sc(f: &[i32], g: &[i32], sparse: &Sparse<P>, c_idx: &[usize], x: &mut [i32], y: &mut [i32]);
+}
+
+/// BLISS-B, `GreedySC` keeps `|Sc|^2` below `PMAX` for every key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct BlissB;
+
+/// The original BLISS, a plain `Sc` and keys rejected when `N_kappa(S)` exceeds `PMAX`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Original;
+
+impl<P: ParameterSet> Variant<P> for BlissB {
+    const NAME: &'static str = "BLISS-B";
+    const KEYGEN_TAG: &'static [u8] = b"";
+
+    const PMAX: i32 = P::PMAX;
+    const B_L2: i32 = P::B_L2;
+
+    fn accept_key(_: &[i32], _: &[i32]) -> bool {
+        true
+    }
+
+    fn sc(f: &[i32], g: &[i32], sparse: &Sparse<P>, c_idx: &[usize], x: &mut [i32], y: &mut [i32]) {
+        greedy_sc_table::<P>(f, g, sparse, c_idx, x, y)
+    }
+}
+
+impl<P: OriginalSet> Variant<P> for Original {
+    const NAME: &'static str = "BLISS";
+    const KEYGEN_TAG: &'static [u8] = b" original ";
+
+    const PMAX: i32 = P::PMAX_ORIGINAL;
+    const B_L2: i32 = P::B_L2_ORIGINAL;
+
+    fn accept_key(f: &[i32], g: &[i32]) -> bool {
+        n_kappa::<P>(f, g) <= P::PMAX_ORIGINAL
+    }
+
+    fn sc(f: &[i32], g: &[i32], _: &Sparse<P>, c_idx: &[usize], x: &mut [i32], y: &mut [i32]) {
+        plain_sc::<P>(f, g, c_idx, x, y)
+    }
+}